@@ -0,0 +1,48 @@
+#!/usr/bin/env python3
+"""Stand-in for a webhook receiver, to try out vigilant --webhook locally.
+
+Prints every delivery and whether its signature is right, see
+docs/webhooks.md. Answering with an error makes vigilant retry.
+
+    scripts/webhook-receiver.py [--port 8787] [--secret SECRET] [--fail N]
+    vigilant --webhook http://127.0.0.1:8787/ --webhook-secret SECRET ...
+"""
+import argparse
+import hashlib
+import hmac
+import json
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+parser.add_argument("--port", type=int, default=8787)
+parser.add_argument("--secret", help="check the signatures against this secret")
+parser.add_argument("--fail", type=int, default=0, metavar="N",
+                    help="answer the first N deliveries with 500, to see retries")
+args = parser.parse_args()
+received = 0
+
+
+class Receiver(BaseHTTPRequestHandler):
+    def do_POST(self):
+        global received
+        received += 1
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        signature = "unsigned"
+        if args.secret:
+            timestamp = self.headers.get("X-Vigilant-Timestamp", "")
+            expected = "sha256=" + hmac.new(args.secret.encode(), timestamp.encode() + b"." + body,
+                                            hashlib.sha256).hexdigest()
+            valid = hmac.compare_digest(expected, self.headers.get("X-Vigilant-Signature", ""))
+            signature = "signature ok" if valid else "BAD SIGNATURE"
+        print(f"{self.headers.get('X-Vigilant-Event')} delivery {self.headers.get('X-Vigilant-Delivery')}"
+              f" ({signature})")
+        print(json.dumps(json.loads(body), indent=2), flush=True)
+        self.send_response(500 if received <= args.fail else 204)
+        self.end_headers()
+
+    def log_message(self, *_):
+        pass
+
+
+print(f"Listening on http://127.0.0.1:{args.port}/", flush=True)
+HTTPServer(("127.0.0.1", args.port), Receiver).serve_forever()
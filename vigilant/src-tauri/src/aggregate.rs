@@ -0,0 +1,239 @@
+use serde::Serialize;
+use crate::filter::Filter;
+use crate::value::{self, CellKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+}
+
+/// An aggregate shown in the footer of the table, such as `sum(%MEM)`,
+/// `max(RSS)`, `count()` or `count(STATE == "ESTAB")`.
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    text: String,
+    func: Func,
+    column: Option<String>,  // Column aggregated, None for a plain count
+    filter: Option<Filter>,  // Rows counted by count(expression)
+}
+
+/// Value of an aggregate, sent to the client as part of the footer.
+#[derive(Serialize, Debug, Clone)]
+pub struct FooterCell {
+    pub label: String,
+    pub column: Option<String>,
+    pub value: Option<f64>,
+    pub text: String,
+}
+
+/// Splits `func(arg)` into the function and its trimmed argument.
+fn split(text: &str) -> Result<(Func, &str), String> {
+    let (name, arg) = text.strip_suffix(')')
+        .and_then(|t| t.split_once('('))
+        .ok_or_else(|| format!("Expected func(column) in {}", text))?;
+    let func = match name.trim().to_lowercase().as_str() {
+        "sum" => Func::Sum,
+        "avg" => Func::Avg,
+        "min" => Func::Min,
+        "max" => Func::Max,
+        "count" => Func::Count,
+        other => return Err(format!("Unknown aggregate {}, use sum, avg, min, max or count", other)),
+    };
+    Ok((func, arg.trim()))
+}
+
+/// Whether an argument can only be a column name, such as `%CPU`, rather
+/// than an expression.
+fn is_name(arg: &str) -> bool {
+    !arg.contains(char::is_whitespace)
+}
+
+impl Aggregate {
+    /// Parses `func(arg)`, checking the columns against the table headers.
+    pub fn parse(text: &str, headers: &[String], kinds: &[CellKind]) -> Result<Aggregate, String> {
+        let text = text.trim();
+        let (func, arg) = split(text)?;
+
+        let mut aggregate = Aggregate { text: text.to_string(), func, column: None, filter: None };
+        if let Some(col) = headers.iter().position(|h| h == arg) {
+            let kind = kinds.get(col).copied().unwrap_or(CellKind::Text);
+            if func != Func::Count && kind == CellKind::Text {
+                return Err(format!("Column {} is not numeric", arg));
+            }
+            aggregate.column = Some(arg.to_string());
+        } else if func == Func::Count {
+            if !arg.is_empty() {
+                let filter = Filter::parse(arg)?;
+                filter.check_columns(headers)?;
+                aggregate.filter = Some(filter);
+            }
+        } else {
+            return Err(format!("No column named {}", arg));
+        }
+        Ok(aggregate)
+    }
+
+    /// Checks `func(arg)` before the table headers are known: the function
+    /// and, for count(expression), the expression.
+    pub fn check(text: &str) -> Result<(), String> {
+        let text = text.trim();
+        match split(text)? {
+            (Func::Count, arg) if !arg.is_empty() && !is_name(arg) => Filter::parse(arg).map(|_| ()),
+            (Func::Count, _) => Ok(()),
+            (_, "") => Err(format!("Expected func(column) in {}", text)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Computes the aggregate over the given table rows.
+    pub fn compute(&self, headers: &[String], kinds: &[CellKind], rows: &[Vec<String>]) -> FooterCell {
+        let col = self.column.as_ref().and_then(|c| headers.iter().position(|h| h == c));
+        let kind = col.and_then(|c| kinds.get(c).copied()).unwrap_or(CellKind::Text);
+
+        let value = match (self.func, col) {
+            (Func::Count, None) => Some(rows.iter()
+                .filter(|row| self.filter.as_ref().is_none_or(|f| f.matches(headers, kinds, row)))
+                .count() as f64),
+            (Func::Count, Some(c)) => Some(rows.iter()
+                .filter(|row| row.get(c).is_some_and(|cell| !cell.is_empty() && cell != "-"))
+                .count() as f64),
+            (_, None) => None,
+            (func, Some(c)) => {
+                let values: Vec<f64> = rows.iter()
+                    .filter_map(|row| row.get(c).and_then(|cell| value::number(cell, kind)))
+                    .collect();
+                if values.is_empty() {
+                    None
+                } else {
+                    match func {
+                        Func::Sum => Some(values.iter().sum()),
+                        Func::Avg => Some(values.iter().sum::<f64>() / values.len() as f64),
+                        Func::Min => values.iter().copied().reduce(f64::min),
+                        Func::Max => values.iter().copied().reduce(f64::max),
+                        Func::Count => Some(values.len() as f64),
+                    }
+                }
+            },
+        };
+
+        let text = match value {
+            Some(v) if self.func == Func::Count => format!("{}", v),
+            Some(v) => format_value(v, kind),
+            None => "-".to_string(),
+        };
+        FooterCell { label: self.text.clone(), column: self.column.clone(), value, text }
+    }
+}
+
+/// Formats a normalized value back in the units of its column. Sizes go by
+/// powers of 1024, K being 1024 bytes, the way value::parse_cell reads them.
+fn format_value(v: f64, kind: CellKind) -> String {
+    match kind {
+        CellKind::Percent => format!("{:.1}%", v),
+        CellKind::Size => {
+            let units = ["B", "K", "M", "G", "T", "P"];
+            let mut v = v;
+            let mut unit = 0;
+            while v.abs() >= 1024.0 && unit < units.len() - 1 {
+                v /= 1024.0;
+                unit += 1;
+            }
+            if unit == 0 { format!("{}B", v) } else { format!("{:.1}{}", v, units[unit]) }
+        },
+        CellKind::Duration => {
+            let secs = v.round() as i64;
+            format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        },
+        CellKind::Timestamp => chrono::DateTime::from_timestamp(v as i64, 0)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| v.to_string()),
+        _ if v.fract() == 0.0 => format!("{}", v),
+        _ => format!("{:.2}", v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        vec!["NAME".to_string(), "STATE".to_string(), "CPU".to_string(), "RSS".to_string()]
+    }
+
+    fn kinds() -> Vec<CellKind> {
+        vec![CellKind::Text, CellKind::Text, CellKind::Integer, CellKind::Size]
+    }
+
+    fn rows() -> Vec<Vec<String>> {
+        [
+            vec!["a", "UP", "10", "512K"],
+            vec!["b", "DOWN", "-", "1M"],
+            vec!["c", "UP", "30", "-"],
+            // A row cut short has nothing in the last columns
+            vec!["d", "UP"],
+        ].into_iter()
+            .map(|row| row.into_iter().map(String::from).collect())
+            .collect()
+    }
+
+    fn compute(text: &str, rows: &[Vec<String>]) -> FooterCell {
+        Aggregate::parse(text, &headers(), &kinds()).unwrap().compute(&headers(), &kinds(), rows)
+    }
+
+    #[test]
+    fn numbers_skip_missing_cells() {
+        let rows = rows();
+        assert_eq!(compute("sum(CPU)", &rows).value, Some(40.0));
+        assert_eq!(compute("avg(CPU)", &rows).value, Some(20.0));
+        assert_eq!(compute("min(CPU)", &rows).value, Some(10.0));
+        assert_eq!(compute("max(CPU)", &rows).value, Some(30.0));
+        assert_eq!(compute("sum(CPU)", &rows).text, "40");
+    }
+
+    #[test]
+    fn counts() {
+        let rows = rows();
+        assert_eq!(compute("count()", &rows).value, Some(4.0));
+        assert_eq!(compute("count(CPU)", &rows).value, Some(2.0));
+        assert_eq!(compute("count(STATE == \"UP\")", &rows).value, Some(3.0));
+        assert_eq!(compute("count(CPU > 15)", &rows).text, "1");
+    }
+
+    #[test]
+    fn empty_tables() {
+        assert_eq!(compute("sum(CPU)", &[]).value, None);
+        assert_eq!(compute("avg(CPU)", &[]).text, "-");
+        assert_eq!(compute("count()", &[]).value, Some(0.0));
+        let dashes = vec![vec!["a".to_string(), "UP".to_string(), "-".to_string(), "-".to_string()]];
+        assert_eq!(compute("max(RSS)", &dashes).text, "-");
+    }
+
+    #[test]
+    fn sizes_keep_their_units() {
+        let rows = rows();
+        assert_eq!(compute("sum(RSS)", &rows).value, Some(1536.0 * 1024.0));
+        assert_eq!(compute("sum(RSS)", &rows).text, "1.5M");
+        // Read back as the cells are
+        assert_eq!(value::parse_cell("1.5M"), (CellKind::Size, Some(1536.0 * 1024.0)));
+        assert_eq!(format_value(100.0, CellKind::Size), "100B");
+        assert_eq!(format_value(3.0 * 1024f64.powi(3), CellKind::Size), "3.0G");
+    }
+
+    #[test]
+    fn bad_aggregates() {
+        assert!(Aggregate::parse("sum(NAME)", &headers(), &kinds()).is_err());
+        assert!(Aggregate::parse("sum(MEM)", &headers(), &kinds()).is_err());
+        assert!(Aggregate::parse("median(CPU)", &headers(), &kinds()).is_err());
+        assert!(Aggregate::check("sum()").is_err());
+        assert!(Aggregate::check("count(STATE ==)").is_err());
+        assert!(Aggregate::check("count(STATE == \"UP\")").is_ok());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use regex::Regex;
+use serde::Serialize;
+use crate::filter::Filter;
+use crate::value::CellKind;
+
+/// An alert rule such as `%CPU > 90 for 3 runs` or `STATE == "FAILED"`:
+/// a filter expression which has to match a row for that many runs in a
+/// row, one when not given, before the alert fires.
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    text: String,
+    filter: Filter,
+    runs: usize,
+    streaks: HashMap<String, usize>,  // Consecutive runs each row matched, by row key
+    firing: Vec<String>,              // Rows the alert last fired for
+}
+
+/// A row which made an alert fire.
+#[derive(Serialize, Debug, Clone)]
+pub struct AlertRow {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+/// Change in the state of an alert after a run.
+#[derive(Debug, Clone)]
+pub enum AlertEvent {
+    /// The alert fired, or fired for other rows than before
+    Fired { rule: String, rows: Vec<AlertRow> },
+    /// No row matches the rule any more
+    Resolved { rule: String },
+}
+
+impl AlertRule {
+    /// Parses `expression [for N runs]`, checking the columns against the
+    /// table headers.
+    pub fn parse(text: &str, headers: &[String]) -> Result<AlertRule, String> {
+        let text = text.trim();
+        let re = Regex::new(r"^(.*?)\s+for\s+(\d+)\s+runs?$").map_err(|e| e.to_string())?;
+        let (expr, runs) = match re.captures(text) {
+            Some(caps) => {
+                let runs = caps[2].parse::<usize>().map_err(|e| format!("Bad run count in {}: {}", text, e))?;
+                (caps.get(1).map_or("", |m| m.as_str()), runs)
+            },
+            None => (text, 1),
+        };
+        if runs == 0 {
+            return Err(format!("An alert needs at least 1 run in {}", text));
+        }
+        let filter = Filter::parse(expr)?;
+        filter.check_columns(headers)?;
+        Ok(AlertRule {
+            text: text.to_string(),
+            filter,
+            runs,
+            streaks: HashMap::new(),
+            firing: Vec::new(),
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Takes the rows of a new run into account. Returns an event when the
+    /// alert fires, fires for other rows, or resolves.
+    pub fn check(&mut self, headers: &[String], kinds: &[CellKind], rows: &[(String, Vec<String>)]) -> Option<AlertEvent> {
+        let mut streaks = HashMap::new();
+        let mut offending = Vec::new();
+        for (key, row) in rows {
+            if !self.filter.matches(headers, kinds, row) {
+                continue;
+            }
+            let n = self.streaks.get(key).copied().unwrap_or(0) + 1;
+            streaks.insert(key.clone(), n);
+            if n >= self.runs {
+                offending.push(AlertRow { key: key.clone(), values: row.clone() });
+            }
+        }
+        // Rows which stopped matching, or went away, start over
+        self.streaks = streaks;
+
+        let keys: Vec<String> = offending.iter().map(|row| row.key.clone()).collect();
+        if keys == self.firing {
+            return None;
+        }
+        self.firing = keys;
+        if offending.is_empty() {
+            Some(AlertEvent::Resolved { rule: self.text.clone() })
+        } else {
+            Some(AlertEvent::Fired { rule: self.text.clone(), rows: offending })
+        }
+    }
+}
+
+/// A pattern looked for in the raw output lines, for commands whose output
+/// is not a table. `--alert-on` fires while some line matches, e.g. `ERROR`,
+/// `--alert-off` while no line does, e.g. `Ready`.
+#[derive(Debug, Clone)]
+pub struct LineTrigger {
+    text: String,
+    pattern: Regex,
+    on_match: bool,  // Fire when the pattern appears rather than disappears
+    firing: bool,
+}
+
+impl LineTrigger {
+    pub fn parse(pattern: &str, on_match: bool) -> Result<LineTrigger, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("Bad pattern {}: {}", pattern, e))?;
+        let text = format!("{} {}", if on_match { "alert-on" } else { "alert-off" }, pattern);
+        Ok(LineTrigger { text, pattern: regex, on_match, firing: false })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Looks for the pattern in the lines of a new run. Returns an event
+    /// when the trigger fires or resolves; the rows of a fired event are the
+    /// matching lines, keyed by line number.
+    pub fn check(&mut self, lines: &[String]) -> Option<AlertEvent> {
+        let matching: Vec<AlertRow> = lines.iter().enumerate()
+            .filter(|(_, line)| self.pattern.is_match(line))
+            .map(|(n, line)| AlertRow { key: (n + 1).to_string(), values: vec![line.clone()] })
+            .collect();
+        let firing = matching.is_empty() != self.on_match;
+        if firing == self.firing {
+            return None;
+        }
+        self.firing = firing;
+        if firing {
+            Some(AlertEvent::Fired { rule: self.text.clone(), rows: matching })
+        } else {
+            Some(AlertEvent::Resolved { rule: self.text.clone() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        vec!["NAME".to_string(), "CPU".to_string()]
+    }
+
+    /// Checks a run of the rule on rows named a, b, ... with the given CPU.
+    fn check(rule: &mut AlertRule, cpus: &[u32]) -> Option<AlertEvent> {
+        let rows: Vec<(String, Vec<String>)> = cpus.iter().enumerate()
+            .map(|(i, cpu)| {
+                let name = ((b'a' + i as u8) as char).to_string();
+                (name.clone(), vec![name, cpu.to_string()])
+            })
+            .collect();
+        rule.check(&headers(), &[CellKind::Text, CellKind::Integer], &rows)
+    }
+
+    fn fired_keys(event: Option<AlertEvent>) -> Vec<String> {
+        match event {
+            Some(AlertEvent::Fired { rows, .. }) => rows.into_iter().map(|row| row.key).collect(),
+            other => panic!("expected the alert to fire, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fires_after_the_runs_given() {
+        let mut rule = AlertRule::parse("CPU > 90 for 3 runs", &headers()).unwrap();
+        assert!(check(&mut rule, &[95, 10]).is_none());
+        assert!(check(&mut rule, &[95, 10]).is_none());
+        assert_eq!(fired_keys(check(&mut rule, &[95, 10])), vec!["a"]);
+        // Still firing for the same rows
+        assert!(check(&mut rule, &[95, 10]).is_none());
+    }
+
+    #[test]
+    fn streak_starts_over_when_a_row_stops_matching() {
+        let mut rule = AlertRule::parse("CPU > 90 for 2 runs", &headers()).unwrap();
+        assert!(check(&mut rule, &[95]).is_none());
+        assert!(check(&mut rule, &[50]).is_none());
+        assert!(check(&mut rule, &[95]).is_none());
+        assert_eq!(fired_keys(check(&mut rule, &[95])), vec!["a"]);
+    }
+
+    #[test]
+    fn fires_again_for_other_rows() {
+        let mut rule = AlertRule::parse("CPU > 90", &headers()).unwrap();
+        assert_eq!(fired_keys(check(&mut rule, &[95, 10])), vec!["a"]);
+        assert_eq!(fired_keys(check(&mut rule, &[95, 99])), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn resolves_and_fires_again() {
+        let mut rule = AlertRule::parse("CPU > 90 for 2 runs", &headers()).unwrap();
+        check(&mut rule, &[95]);
+        assert_eq!(fired_keys(check(&mut rule, &[95])), vec!["a"]);
+        assert!(matches!(check(&mut rule, &[10]), Some(AlertEvent::Resolved { .. })));
+        assert!(check(&mut rule, &[10]).is_none());
+        // The streak is counted anew before firing again
+        assert!(check(&mut rule, &[95]).is_none());
+        assert_eq!(fired_keys(check(&mut rule, &[95])), vec!["a"]);
+    }
+
+    #[test]
+    fn bad_rules_are_reported() {
+        assert!(AlertRule::parse("CPU > 90 for 0 runs", &headers()).is_err());
+        assert!(AlertRule::parse("MEM > 90", &headers()).is_err());
+    }
+
+    #[test]
+    fn line_triggers() {
+        let lines = |text: &str| text.split(' ').map(String::from).collect::<Vec<String>>();
+        let mut on = LineTrigger::parse("ERR", true).unwrap();
+        assert!(on.check(&lines("ok ok")).is_none());
+        assert_eq!(fired_keys(on.check(&lines("ok ERROR"))), vec!["2"]);
+        assert!(matches!(on.check(&lines("ok ok")), Some(AlertEvent::Resolved { .. })));
+
+        let mut off = LineTrigger::parse("^Ready$", false).unwrap();
+        assert!(off.check(&lines("Ready")).is_none());
+        assert!(matches!(off.check(&lines("Starting")), Some(AlertEvent::Fired { .. })));
+
+        assert!(LineTrigger::parse("ERR(", true).is_err());
+    }
+}
@@ -1,19 +1,309 @@
-use std::process::{Command, exit};
-use std::io::{self, Write};
+use std::process::{Command, Stdio, exit};
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::thread;
 use chrono::Utc;
 use std::vec::Vec;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use crate::value::{self, CellKind};
+use crate::filter::Filter;
+use crate::history::{CellHistory, PointsByRow, Run, RunHistory, SeriesByKey, ValuesByRow};
+use crate::store::RunStore;
+use crate::session::Recorder;
+use std::sync::{Arc, Mutex};
+use crate::aggregate::{Aggregate, FooterCell};
+use crate::alert::{AlertEvent, AlertRule, LineTrigger};
+
+/// Number of runs kept in memory.
+const RUN_LIMIT: usize = 1000;
+
+/// How long the output of a command killed on timeout is still read.
+const KILL_GRACE: Duration = Duration::from_millis(200);
+
+/// Returns the current time in seconds since the epoch.
+fn now_secs() -> f64 {
+    Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+/// Runs a command, capturing its output, exit status and how long it took.
+/// A command still running after the timeout is killed, which the returned
+/// flag tells.
+fn run_command(name: &str, args: &[String], timeout: Option<Duration>) -> Result<(Run, bool), io::Error> {
+    let timestamp = now_secs();
+    let started = Instant::now();
+    let mut child = Command::new(name).args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Both pipes are read while waiting, a command filling one would block
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let result = match pipe {
+                Some(mut pipe) => pipe.read_to_end(&mut bytes).map(|_| bytes),
+                None => Ok(bytes),
+            };
+            let _ = sender.send(result);
+        });
+        receiver
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let mut timed_out = false;
+    let status = match timeout {
+        None => child.wait()?,
+        Some(limit) => loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() >= limit {
+                // It may have just exited, which wait tells
+                let _ = child.kill();
+                timed_out = true;
+                break child.wait()?;
+            }
+            thread::sleep(Duration::from_millis(10));
+        },
+    };
+    // Children of a killed command can keep the pipes open, their output is
+    // only waited for a little
+    let collect = |receiver: mpsc::Receiver<io::Result<Vec<u8>>>| if timed_out {
+        receiver.recv_timeout(KILL_GRACE).unwrap_or_else(|_| Ok(Vec::new()))
+    } else {
+        receiver.recv().unwrap_or_else(|_| Err(io::Error::other("Reading the output failed")))
+    };
+    let stdout = collect(stdout)?;
+    let stderr = collect(stderr)?;
+
+    let lines = |bytes: &[u8]| String::from_utf8_lossy(bytes).lines().map(String::from).collect();
+    let run = Run {
+        id: 0,
+        timestamp,
+        duration: started.elapsed().as_secs_f64(),
+        status: status.code(),
+        lines: lines(&stdout),
+        stderr: lines(&stderr),
+    };
+    Ok((run, timed_out))
+}
+
+/// Error for a run which did not succeed.
+fn run_error(run: &Run, timeout: Option<Duration>, timed_out: bool) -> io::Error {
+    match (timed_out, timeout, run.status) {
+        (true, Some(limit), _) => io::Error::new(io::ErrorKind::TimedOut,
+            format!("Command timed out after {}s", limit.as_secs_f64())),
+        (_, _, Some(code)) => io::Error::other(format!("Command failed with status {}", code)),
+        _ => io::Error::other("Command failed, killed by a signal"),
+    }
+}
+
+/// Splits lines of output into columns.
+fn split_lines(lines: &[String]) -> Vec<Vec<String>> {
+    lines.iter()
+        .map(|line| line.split_whitespace().map(String::from).collect())
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Column the table rows are ordered by.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SortSpec {
+    pub column: String,
+    pub direction: SortDirection,
+}
 
 /// Struct to store information about command execution.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct CmdOutput {
     pub cmdline: String,
     pub timestamp: String,        // The timestamp when the command was executed
     pub output: Vec<String>,      // The output of the command, stored in a Vec
         cmdname: String,
-        //cmdargs: Vec<str>,
+        cmdargs: Vec<String>,
         current: usize,
         headers: Option<Vec<String>>,
         outcols: Option<Vec<Vec<String>>>,
+        kinds: Vec<CellKind>,
+        sort: Option<SortSpec>,
+        filter: Option<Filter>,
+        columns: Option<Vec<ColumnSpec>>,
+        key: Option<String>,              // Column identifying a row, first one when None
+        rate_columns: Vec<String>,        // Counters to derive a per-second rate for
+        counters: HashMap<(String, String), (f64, f64)>,  // Last value and time by row key and column
+        rates: HashMap<(String, String), f64>,
+        sampled: f64,                     // When the output was captured, seconds since the epoch
+        view_kinds: Vec<CellKind>,
+        history: CellHistory,             // Recent values of every numeric cell
+        recorded: Option<f64>,            // Output last added to the history
+        view_keys: Vec<String>,           // Key of each row in the view
+        runs: RunHistory,                 // Recent runs, to look back at
+        store: Option<RunStore>,          // Every run, kept on disk
+        recorder: Option<Arc<Mutex<Recorder>>>,  // Recording shared by every client
+        aggregates: Vec<Aggregate>,       // Shown in the footer
+        footer: Vec<FooterCell>,
+        alerts: Vec<AlertRule>,
+        triggers: Vec<LineTrigger>,       // Patterns looked for in the raw lines
+        alerted: Option<f64>,             // Output the alerts were last checked against
+        group_by: Option<String>,         // Column rows are grouped by
+        timeout: Option<Duration>,        // Runs taking longer are killed
+        view: Vec<Vec<String>>,   // Table rows as last sent to the client
+}
+
+/// A column to show, optionally under a different label.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ColumnSpec {
+    pub name: String,
+    pub label: Option<String>,
+}
+
+impl ColumnSpec {
+    /// Parses a comma separated list such as `PID,COMMAND=Command,%CPU`.
+    pub fn parse_list(text: &str) -> Vec<ColumnSpec> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(|c| match c.split_once('=') {
+                Some((name, label)) => ColumnSpec { name: name.to_string(), label: Some(label.to_string()) },
+                None => ColumnSpec { name: c.to_string(), label: None },
+            })
+            .collect()
+    }
+}
+
+/// A row of a group, as sent to the client.
+#[derive(Serialize, Debug, Clone)]
+pub struct GroupRow {
+    pub key: String,
+    pub values: Vec<String>,
+    pub numbers: Vec<Option<f64>>,
+}
+
+/// Rows sharing the same value in the group column.
+#[derive(Serialize, Debug, Clone)]
+pub struct Group {
+    pub value: String,
+    pub count: usize,
+    pub aggregates: Vec<FooterCell>,
+    pub rows: Vec<GroupRow>,
+}
+
+/// Value of a cell in one run, a row of the long format history export.
+#[derive(Serialize, Debug, Clone)]
+pub struct HistoryPoint {
+    pub timestamp: f64,
+    pub row_key: String,
+    pub column: String,
+    pub value: f64,
+}
+
+/// A past run shown as a table, with the cells which changed since the run
+/// before it.
+#[derive(Serialize, Debug, Clone)]
+pub struct Snapshot {
+    pub run_id: u64,
+    pub timestamp: f64,
+    pub duration: f64,
+    pub status: Option<i32>,
+    pub lines: Vec<String>,
+    pub stderr: Vec<String>,
+    pub changed_lines: Vec<usize>,  // Lines which differ from the previous run
+    pub headers: Vec<String>,
+    pub keys: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub changed: Vec<Vec<usize>>,   // Cells of each row which differ from the previous run
+}
+
+/// Differences between two runs, rows being matched by key.
+#[derive(Serialize, Debug, Clone)]
+pub struct RunDiff {
+    pub from: u64,
+    pub to: u64,
+    pub from_timestamp: f64,
+    pub to_timestamp: f64,
+    pub old_headers: Vec<String>,
+    pub headers: Vec<String>,
+    pub added: Vec<DiffRow>,
+    pub removed: Vec<DiffRow>,
+    pub changed: Vec<ChangedRow>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DiffRow {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangedRow {
+    pub key: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+    pub cells: Vec<usize>,  // Cells which differ
+}
+
+/// A run split into columns, with the key of each row.
+struct RunTable {
+    headers: Vec<String>,
+    keys: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Rows of two tables told apart by diff_tables, as indices into them.
+struct TableDiff {
+    added: Vec<usize>,                      // Rows of the new table
+    removed: Vec<usize>,                    // Rows of the old table
+    changed: Vec<(usize, usize, Vec<usize>)>,  // Old row, new row and the cells which differ
+}
+
+/// Matches the rows of two tables by key and compares their cells the way
+/// update_lines compares lines. Once the columns changed cells cannot be
+/// lined up, so every cell of a row counts as changed.
+fn diff_tables(old: &RunTable, new: &RunTable) -> TableDiff {
+    let old_rows: HashMap<&String, usize> = old.keys.iter().enumerate().map(|(i, k)| (k, i)).collect();
+    let new_keys: HashSet<&String> = new.keys.iter().collect();
+    let mut rows = TableDiff { added: Vec::new(), removed: Vec::new(), changed: Vec::new() };
+
+    for (n, key) in new.keys.iter().enumerate() {
+        match old_rows.get(key) {
+            Some(&o) => {
+                let cells = if old.headers == new.headers {
+                    diff(&old.rows[o], &new.rows[n])
+                } else {
+                    (0..new.rows[n].len()).collect()
+                };
+                if !cells.is_empty() {
+                    rows.changed.push((o, n, cells));
+                }
+            },
+            None => rows.added.push(n),
+        }
+    }
+    rows.removed = old.keys.iter().enumerate()
+        .filter(|(_, k)| !new_keys.contains(k))
+        .map(|(i, _)| i)
+        .collect();
+    rows
+}
+
+/// Returns the indices at which two sequences differ, including the indices
+/// only present in the longer one.
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<usize> {
+    let common = old.len().min(new.len());
+    let mut changed: Vec<usize> = (0..common).filter(|&i| old[i] != new[i]).collect();
+    changed.extend(common..old.len().max(new.len()));
+    changed
 }
 
 impl CmdOutput {
@@ -21,7 +311,7 @@ impl CmdOutput {
     /// It executes the command, captures the output, and stores it in the struct.
     pub fn new(cmdline: &str) -> Result<Self, io::Error> {
         // Split the cmdline string into arguments
-        let args: Vec<&str> = cmdline.split_whitespace().collect();
+        let args: Vec<String> = cmdline.split_whitespace().map(String::from).collect();
 
         // Ensure that there is at least one argument (the command itself)
         if args.is_empty() {
@@ -29,34 +319,15 @@ impl CmdOutput {
         }
 
         // Execute the command
-        let output = Command::new(args[0])
-            .args(&args[1..])
-            .output(); // Execute the command and capture output
+        let output = run_command(&args[0], &args[1..], None);
 
         match output {
-            Ok(output) => {
-                if !output.status.success() {
+            Ok((run, timed_out)) => {
+                if run.status != Some(0) {
                     // If the command failed, return an error
-                    return Err(io::Error::new(io::ErrorKind::Other, "Command failed"));
+                    return Err(run_error(&run, None, timed_out));
                 }
-
-                // Capture the standard output as a string and split it into lines
-                let output_lines = String::from_utf8_lossy(&output.stdout)
-                    .lines()
-                    .map(|line| line.to_string())
-                    .collect::<Vec<String>>();
-
-                // Create and return the CmdOutput struct
-                Ok(CmdOutput {
-                    cmdline: cmdline.to_string(),
-                    timestamp: Utc::now().to_rfc3339(), // Timestamp when the command was executed
-                    output: output_lines,
-                    headers: None,
-                    outcols: None,
-                    cmdname: args[0].to_string(),
-                    //cmdargs: args[1..].to_vec()
-                    current: 0
-                })
+                Ok(CmdOutput::from_run(cmdline, run))
             }
             Err(e) => {
                 // If there was an error with the command execution itself
@@ -65,6 +336,52 @@ impl CmdOutput {
         }
     }
 
+    /// Creates a CmdOutput from a run which already happened, such as one
+    /// read back from a recording.
+    pub fn from_run(cmdline: &str, run: Run) -> Self {
+        let args: Vec<String> = cmdline.split_whitespace().map(String::from).collect();
+        let output_lines = run.lines.clone();
+        let sampled = run.timestamp;
+
+        // Create and return the CmdOutput struct
+        let mut runs = RunHistory::new(RUN_LIMIT);
+        runs.push(run);
+        CmdOutput {
+            cmdline: cmdline.to_string(),
+            timestamp: Utc::now().to_rfc3339(), // Timestamp when the command was executed
+            output: output_lines,
+            headers: None,
+            outcols: None,
+            kinds: Vec::new(),
+            sort: None,
+            filter: None,
+            columns: None,
+            key: None,
+            rate_columns: Vec::new(),
+            counters: HashMap::new(),
+            rates: HashMap::new(),
+            sampled,
+            view_kinds: Vec::new(),
+            history: CellHistory::new(60),
+            recorded: None,
+            view_keys: Vec::new(),
+            runs,
+            store: None,
+            recorder: None,
+            aggregates: Vec::new(),
+            footer: Vec::new(),
+            alerts: Vec::new(),
+            triggers: Vec::new(),
+            alerted: None,
+            group_by: None,
+            timeout: None,
+            view: Vec::new(),
+            cmdname: args.first().cloned().unwrap_or_default(),
+            cmdargs: args.get(1..).unwrap_or_default().to_vec(),
+            current: 0
+        }
+    }
+
     //pub fn set_cmdline(mut self, cmdline: String) -> Result<T, E> {
     //    self.cmdline = cmdline;
 
@@ -79,26 +396,20 @@ impl CmdOutput {
         }
 
         // Execute the command
-        let output = Command::new(self.cmdname.clone())
-            //.args(&args[1..])
-            .output();
+        let output = run_command(&self.cmdname, &self.cmdargs, self.timeout);
 
         self.current = 0;
         match output {
-            Ok(output) => {
-                if !output.status.success() {
+            Ok((run, timed_out)) => {
+                // Failed runs are kept too, they are often the interesting ones
+                let success = run.status == Some(0) && !timed_out;
+                let output_lines = run.lines.clone();
+                let error = run_error(&run, self.timeout, timed_out);
+                self.record_run(run);
+                if !success {
                     // If the command failed, return an error
-                    return Err(io::Error::new(io::ErrorKind::Other, "Command failed"));
+                    return Err(error);
                 }
-
-                // Capture the standard output as a string and split it into lines
-                let output_lines = String::from_utf8_lossy(&output.stdout)
-                    .lines()
-                    .map(|line| line.to_string())
-                    .collect::<Vec<String>>();
-
-                // Create and return the CmdOutput struct
-                //CmdOutput::new(cmdline)
                 Ok(output_lines)
             }
             Err(e) => {
@@ -108,6 +419,58 @@ impl CmdOutput {
         }
     }
 
+    /// Keeps every run in the given store from now on. The runs already in
+    /// the store become the recent history, looked back at by queries.
+    pub fn set_store(&mut self, store: RunStore) -> Result<(), io::Error> {
+        let stored = store.tail(RUN_LIMIT)?;
+        let pending = self.runs.resume(stored, store.last_id());
+        self.store = Some(store);
+        for run in pending {
+            self.record_run(run);
+        }
+        Ok(())
+    }
+
+    /// Writes every run to a recording from now on, starting with the one
+    /// the current output comes from.
+    pub fn set_recorder(&mut self, recorder: Arc<Mutex<Recorder>>) {
+        self.recorder = Some(recorder);
+        if let Some(id) = self.runs.last_id() {
+            self.write_recording(id);
+        }
+    }
+
+    fn write_recording(&self, id: u64) {
+        if let (Some(recorder), Some(run)) = (self.recorder.as_ref(), self.runs.get(id)) {
+            if let Err(e) = recorder.lock().unwrap().record(run) {
+                eprintln!("Failed to record run {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Adds a run which did not come from executing the command, such as
+    /// one replayed from a recording, failing like execute when it did.
+    pub fn push_run(&mut self, run: Run) -> Result<Vec<String>, io::Error> {
+        let success = run.status == Some(0);
+        let output_lines = run.lines.clone();
+        let error = run_error(&run, None, false);
+        self.record_run(run);
+        if !success {
+            return Err(error);
+        }
+        Ok(output_lines)
+    }
+
+    fn record_run(&mut self, run: Run) {
+        let id = self.runs.push(run);
+        if let (Some(store), Some(run)) = (self.store.as_mut(), self.runs.get(id)) {
+            if let Err(e) = store.append(run) {
+                eprintln!("Failed to store run {}: {}", id, e);
+            }
+        }
+        self.write_recording(id);
+    }
+
     pub fn get_headers(&mut self) -> Result<Vec<String>, io::Error> {
         // Ensure that there is at least one argument (the command itself)
         if self.cmdname.is_empty() {
@@ -125,35 +488,788 @@ impl CmdOutput {
         Err(io::Error::new(io::ErrorKind::InvalidData, "Headers are not all uppercase"))
     }
 
-    pub fn update_lines(&mut self, oplines: Vec<String>) -> Result<Vec<usize>, String> {
-        let mut changed =  Vec::new();
+    /// Returns true when the output looks like a table, i.e. the first line
+    /// is an all-uppercase header with more than one column. Everything else
+    /// (`date`, `cat /proc/meminfo`, `git log --graph`) is shown as raw text.
+    pub fn is_tabular(&mut self) -> bool {
+        match self.get_headers() {
+            Ok(hdrs) => hdrs.len() > 1,
+            Err(_) => false,
+        }
+    }
+
+    /// Infers the kind of every header column from the data rows below it.
+    pub fn get_kinds(&mut self) -> Vec<CellKind> {
+        let hdrs = match self.get_headers() {
+            Ok(hdrs) => hdrs,
+            Err(_) => return Vec::new(),
+        };
+        let rows: Vec<Vec<&str>> = self.output.iter().skip(1)
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        (0..hdrs.len())
+            .map(|col| value::infer_kind(rows.iter().filter_map(|r| r.get(col).copied())))
+            .collect()
+    }
+
+    /// Returns the headers of the table, i.e. the command output headers with
+    /// a `COLUMN/s` column following each column a rate is computed for.
+    pub fn table_headers(&mut self) -> Vec<String> {
+        let mut table = Vec::new();
+        for hdr in self.get_headers().unwrap_or_default() {
+            let rate = self.rate_columns.contains(&hdr);
+            table.push(hdr.clone());
+            if rate {
+                table.push(format!("{}/s", hdr));
+            }
+        }
+        table
+    }
+
+    fn table_kinds(&mut self) -> Vec<CellKind> {
+        let hdrs = self.get_headers().unwrap_or_default();
+        let mut table = Vec::new();
+        for (col, kind) in self.get_kinds().into_iter().enumerate() {
+            table.push(kind);
+            if self.rate_columns.contains(&hdrs[col]) {
+                table.push(CellKind::Float);
+            }
+        }
+        table
+    }
+
+    /// Sets the column identifying a row across runs, None uses the first one.
+    pub fn set_key(&mut self, key: Option<String>) -> Result<(), String> {
+        if let Some(ref name) = key {
+            let hdrs = self.get_headers().map_err(|e| e.to_string())?;
+            if !hdrs.contains(name) {
+                return Err(format!("No column named {}", name));
+            }
+        }
+        self.key = key;
+        Ok(())
+    }
+
+    /// Returns a key for each data row, made unique by numbering repeats, so
+    /// that values can be followed from one run to the next.
+    fn row_keys(&self, rows: &[Vec<String>]) -> Vec<String> {
+        let hdrs = self.headers.clone().unwrap_or_default();
+        self.row_keys_for(&hdrs, rows)
+    }
+
+    /// Same as row_keys for rows under other headers, such as a past run's.
+    fn row_keys_for(&self, hdrs: &[String], rows: &[Vec<String>]) -> Vec<String> {
+        let col = self.key.as_ref()
+            .and_then(|k| hdrs.iter().position(|h| h == k))
+            .unwrap_or(0);
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        rows.iter().map(|row| {
+            let key = row.get(col).cloned().unwrap_or_default();
+            let n = seen.entry(key.clone()).or_insert(0);
+            *n += 1;
+            if *n > 1 { format!("{}#{}", key, n) } else { key }
+        }).collect()
+    }
+
+    /// Sets the counter columns to derive a per-second rate for.
+    pub fn set_rates(&mut self, columns: Vec<String>) -> Result<(), String> {
+        let hdrs = self.get_headers().map_err(|e| e.to_string())?;
+        if let Some(c) = columns.iter().find(|c| !hdrs.contains(c)) {
+            return Err(format!("No column named {}", c));
+        }
+        self.rate_columns = columns;
+        // Drop what was kept for the columns no longer watched
+        let watched = self.rate_columns.clone();
+        self.counters.retain(|(_, column), _| watched.contains(column));
+        self.rates.retain(|(_, column), _| watched.contains(column));
+        // Start counting from the output at hand, keeping the rates known so far
+        let rates = self.rates.clone();
+        self.update_rates();
+        self.rates.extend(rates);
+        Ok(())
+    }
+
+    pub fn get_rates(&self) -> Vec<String> {
+        self.rate_columns.clone()
+    }
+
+    /// Computes the rate of every watched counter from the value it had on the
+    /// previous run. A counter going backwards either wrapped around the 32 or
+    /// 64 bits it fits in, or was reset when it went back further than half of
+    /// them, in which case there is no rate for this run.
+    fn update_rates(&mut self) {
+        if self.rate_columns.is_empty() {
+            return;
+        }
+        let now = self.sampled;
+        let hdrs = self.get_headers().unwrap_or_default();
+        let kinds = self.get_kinds();
+        let rows = self.split_rows();
+        let keys = self.row_keys(&rows);
+        let mut counters = HashMap::new();
+        let mut rates = HashMap::new();
+
+        for (row, key) in rows.iter().zip(keys) {
+            for column in &self.rate_columns {
+                let col = match hdrs.iter().position(|h| h == column) {
+                    Some(col) => col,
+                    None => continue,
+                };
+                let kind = kinds.get(col).copied().unwrap_or(CellKind::Text);
+                let cur = match row.get(col).and_then(|v| value::number(v, kind)) {
+                    Some(cur) => cur,
+                    None => continue,
+                };
+                let id = (key.clone(), column.clone());
+                if let Some(&(prev, then)) = self.counters.get(&id) {
+                    let secs = now - then;
+                    let delta = if cur >= prev {
+                        Some(cur - prev)
+                    } else {
+                        let range = if prev <= u32::MAX as f64 { u32::MAX as f64 + 1.0 } else { u64::MAX as f64 + 1.0 };
+                        let wrapped = cur + (range - prev);
+                        if wrapped < range / 2.0 { Some(wrapped) } else { None }
+                    };
+                    if let Some(delta) = delta.filter(|_| secs > 0.0) {
+                        rates.insert(id.clone(), delta / secs);
+                    }
+                }
+                counters.insert(id, (cur, now));
+            }
+        }
+        // Rows which went away are forgotten along with their counters
+        self.counters = counters;
+        self.rates = rates;
+    }
+
+    /// Splits the data lines of the output into columns.
+    fn split_rows(&self) -> Vec<Vec<String>> {
+        split_lines(self.output.get(1..).unwrap_or_default())
+    }
 
-        for oi in 0..self.output.len() {
-            if self.output[oi] != oplines[oi] {
-                println!("Found a new update on line numbered {} is {}", oi, oplines[oi]);
-                println!("Found a old update on line numbered {} is {}", oi, self.output[oi]);
-                self.output[oi] = oplines[oi].clone();
-                println!("Now line is {}", self.output[oi]);
-                changed.push(oi);
+    /// Sets the column the table rows are ordered by, None keeps command order.
+    pub fn set_sort(&mut self, sort: Option<SortSpec>) -> Result<(), String> {
+        if let Some(ref spec) = sort {
+            let hdrs = self.table_headers();
+            if !hdrs.contains(&spec.column) {
+                return Err(format!("No column named {}", spec.column));
             }
         }
-        let mut start: usize = 0;
-        let mut end: usize;
-        if oplines.len() > self.output.len() {
-            start = self.output.len();
-            end = oplines.len();
-            for oi in start..end {
-                changed.push(oi);
+        self.sort = sort;
+        Ok(())
+    }
+
+    pub fn get_sort(&self) -> Option<SortSpec> {
+        self.sort.clone()
+    }
+
+    /// Sets the filter rows must pass to be sent, None sends every row.
+    pub fn set_filter(&mut self, text: Option<&str>) -> Result<(), String> {
+        let filter = match text {
+            Some(text) => {
+                let filter = Filter::parse(text)?;
+                self.get_headers().map_err(|e| e.to_string())?;
+                filter.check_columns(&self.table_headers())?;
+                Some(filter)
+            },
+            None => None,
+        };
+        self.filter = filter;
+        Ok(())
+    }
+
+    pub fn get_filter(&self) -> Option<String> {
+        self.filter.as_ref().map(|f| f.text().to_string())
+    }
+
+    /// Selects, orders and renames the columns sent, None sends them all as is.
+    pub fn set_columns(&mut self, columns: Option<Vec<ColumnSpec>>) -> Result<(), String> {
+        if let Some(ref cols) = columns {
+            self.get_headers().map_err(|e| e.to_string())?;
+            let hdrs = self.table_headers();
+            if cols.is_empty() {
+                return Err("No columns selected".to_string());
             }
-        } else if oplines.len() < self.output.len() {
-            start = oplines.len();
-            end = self.output.len();
-            for oi in start..end {
-                changed.push(oi);
+            if let Some(c) = cols.iter().find(|c| !hdrs.contains(&c.name)) {
+                return Err(format!("No column named {}", c.name));
             }
         }
+        self.columns = columns;
+        Ok(())
+    }
+
+    pub fn get_columns(&self) -> Option<Vec<ColumnSpec>> {
+        self.columns.clone()
+    }
+
+    /// Returns the table column index of every column sent to the client.
+    fn projection(&mut self) -> Vec<usize> {
+        let hdrs = self.table_headers();
+        match self.columns {
+            Some(ref cols) => cols.iter()
+                .filter_map(|c| hdrs.iter().position(|h| *h == c.name))
+                .collect(),
+            None => (0..hdrs.len()).collect(),
+        }
+    }
+
+    /// Returns the source names, the labels and the kinds of the columns sent.
+    pub fn get_view_headers(&mut self) -> (Vec<String>, Vec<String>, Vec<CellKind>) {
+        let hdrs = self.table_headers();
+        let kinds = self.table_kinds();
+        let mut names = Vec::new();
+        let mut labels = Vec::new();
+        let mut view_kinds = Vec::new();
+        for col in self.projection() {
+            let label = self.columns.as_ref()
+                .and_then(|cols| cols.iter().find(|c| c.name == hdrs[col]))
+                .and_then(|c| c.label.clone());
+            names.push(hdrs[col].clone());
+            labels.push(label.unwrap_or_else(|| hdrs[col].clone()));
+            view_kinds.push(kinds.get(col).copied().unwrap_or(CellKind::Text));
+        }
+        (names, labels, view_kinds)
+    }
+
+    /// Forgets what was sent so that the next update sends every row again.
+    pub fn reset_view(&mut self) {
+        self.view.clear();
+    }
 
+    /// Splits the data lines into rows and adds the rate columns. Each row
+    /// comes with its key.
+    fn table_rows(&mut self) -> Vec<(String, Vec<String>)> {
+        let source = self.get_headers().unwrap_or_default();
+        let rows = self.split_rows();
+        let keys = self.row_keys(&rows);
+
+        keys.into_iter().zip(rows).map(|(key, row)| {
+            if self.rate_columns.is_empty() {
+                return (key, row);
+            }
+            let mut table = Vec::new();
+            for (col, cell) in row.into_iter().enumerate() {
+                let column = source.get(col).cloned().unwrap_or_default();
+                let rate = self.rate_columns.contains(&column);
+                table.push(cell);
+                if rate {
+                    match self.rates.get(&(key.clone(), column)) {
+                        Some(r) => table.push(format!("{:.1}", r)),
+                        None => table.push("-".to_string()),
+                    }
+                }
+            }
+            (key, table)
+        }).collect()
+    }
+
+    /// Returns the table rows passing the filter.
+    fn filtered_rows(&mut self) -> Vec<(String, Vec<String>)> {
+        let hdrs = self.table_headers();
+        let mut rows = self.table_rows();
+        if let Some(ref filter) = self.filter {
+            rows.retain(|(_, row)| filter.matches(&hdrs, &self.kinds, row));
+        }
+        rows
+    }
+
+    /// Returns the table rows passing the filter, ordered by the sort column
+    /// using the typed values so that `9` sorts before `10` and `512K` before
+    /// `1.5G`.
+    fn sorted_rows(&mut self) -> Vec<(String, Vec<String>)> {
+        let hdrs = self.table_headers();
+        let mut rows = self.filtered_rows();
+
+        if let Some(spec) = self.sort.clone() {
+            if let Some(col) = hdrs.iter().position(|h| *h == spec.column) {
+                let kind = self.kinds.get(col).copied().unwrap_or(CellKind::Text);
+                let mut keyed: Vec<_> = rows.into_iter()
+                    .map(|r| (r.1.get(col).and_then(|v| value::number(v, kind)), r))
+                    .collect();
+                keyed.sort_by(|(an, (_, a)), (bn, (_, b))| {
+                    let ord = match (an, bn) {
+                        (Some(x), Some(y)) => x.partial_cmp(y).unwrap_or(Ordering::Equal),
+                        (Some(_), None) => Ordering::Less,
+                        (None, Some(_)) => Ordering::Greater,
+                        (None, None) => a.get(col).cmp(&b.get(col)),
+                    };
+                    match spec.direction {
+                        SortDirection::Asc => ord,
+                        SortDirection::Desc => ord.reverse(),
+                    }
+                });
+                rows = keyed.into_iter().map(|(_, r)| r).collect();
+            }
+        }
+        rows
+    }
+
+    /// Returns the rows to send, i.e. the sorted rows with their columns
+    /// projected. Projecting last lets the filter, the sort and the grouping
+    /// use columns which are not shown.
+    fn build_rows(&mut self) -> Vec<(String, Vec<String>)> {
+        let mut rows = self.sorted_rows();
+        if self.columns.is_some() {
+            let projection = self.projection();
+            rows = rows.into_iter()
+                .map(|(key, r)| (key, projection.iter().map(|&col| r.get(col).cloned().unwrap_or_default()).collect()))
+                .collect();
+        }
+        rows
+    }
+
+    /// Sets the column rows are grouped by, None shows a flat table.
+    pub fn set_group_by(&mut self, column: Option<String>) -> Result<(), String> {
+        if let Some(ref name) = column {
+            self.get_headers().map_err(|e| e.to_string())?;
+            if !self.table_headers().contains(name) {
+                return Err(format!("No column named {}", name));
+            }
+        }
+        self.group_by = column;
+        Ok(())
+    }
+
+    pub fn get_group_by(&self) -> Option<String> {
+        self.group_by.clone()
+    }
+
+    /// Returns the rows grouped by the value of the group column, in the
+    /// order the groups first appear in the sorted rows, each with its row
+    /// count and the footer aggregates computed over its rows.
+    pub fn get_groups(&mut self) -> Vec<Group> {
+        let column = match self.group_by.clone() {
+            Some(column) => column,
+            None => return Vec::new(),
+        };
+        let hdrs = self.table_headers();
+        let col = match hdrs.iter().position(|h| *h == column) {
+            Some(col) => col,
+            None => return Vec::new(),
+        };
+        let projection = self.projection();
+
+        let mut groups: Vec<(String, Vec<_>)> = Vec::new();
+        for (key, row) in self.sorted_rows() {
+            let value = row.get(col).cloned().unwrap_or_default();
+            match groups.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, rows)) => rows.push((key, row)),
+                None => groups.push((value, vec![(key, row)])),
+            }
+        }
+
+        groups.into_iter().map(|(value, rows)| {
+            let table: Vec<Vec<String>> = rows.iter().map(|(_, row)| row.clone()).collect();
+            let aggregates = self.aggregates.iter()
+                .map(|a| a.compute(&hdrs, &self.kinds, &table))
+                .collect();
+            let rows = rows.into_iter().map(|(key, row)| {
+                let values: Vec<String> = projection.iter()
+                    .map(|&c| row.get(c).cloned().unwrap_or_default())
+                    .collect();
+                let numbers = value::numbers(&values, &self.view_kinds);
+                GroupRow { key, values, numbers }
+            }).collect();
+            Group { value, count: table.len(), aggregates, rows }
+        }).collect()
+    }
+
+    /// Sets the aggregates shown in the footer, e.g. `sum(%MEM)`.
+    pub fn set_aggregates(&mut self, texts: Vec<String>) -> Result<(), String> {
+        self.get_headers().map_err(|e| e.to_string())?;
+        let hdrs = self.table_headers();
+        let kinds = self.table_kinds();
+        let aggregates = texts.iter()
+            .map(|t| Aggregate::parse(t, &hdrs, &kinds))
+            .collect::<Result<Vec<Aggregate>, String>>()?;
+        self.aggregates = aggregates;
+        Ok(())
+    }
+
+    pub fn get_aggregates(&self) -> Vec<String> {
+        self.aggregates.iter().map(|a| a.text().to_string()).collect()
+    }
+
+    /// Sets the alert rules, e.g. `%CPU > 90 for 3 runs`.
+    pub fn set_alerts(&mut self, texts: Vec<String>) -> Result<(), String> {
+        self.get_headers().map_err(|e| e.to_string())?;
+        let hdrs = self.table_headers();
+        let alerts = texts.iter()
+            .map(|t| AlertRule::parse(t, &hdrs))
+            .collect::<Result<Vec<AlertRule>, String>>()?;
+        self.alerts = alerts;
+        self.alerted = None;
+        Ok(())
+    }
+
+    pub fn get_alerts(&self) -> Vec<String> {
+        self.alerts.iter().map(|a| a.text().to_string())
+            .chain(self.triggers.iter().map(|t| t.text().to_string()))
+            .collect()
+    }
+
+    /// Sets the patterns looked for in the raw output lines, tabular or not.
+    pub fn set_triggers(&mut self, triggers: Vec<LineTrigger>) {
+        self.triggers = triggers;
+        self.alerted = None;
+    }
+
+    /// Checks the alert rules against every row of the latest output,
+    /// filtered out or not, and the triggers against its lines. Each output
+    /// is only checked once, so that runs are counted right however often
+    /// the view is updated.
+    pub fn check_alerts(&mut self) -> Vec<AlertEvent> {
+        if (self.alerts.is_empty() && self.triggers.is_empty()) || self.alerted == Some(self.sampled) {
+            return Vec::new();
+        }
+        self.alerted = Some(self.sampled);
+        let mut events: Vec<AlertEvent> = self.triggers.iter_mut()
+            .filter_map(|trigger| trigger.check(&self.output))
+            .collect();
+        if !self.alerts.is_empty() {
+            let hdrs = self.table_headers();
+            let kinds = self.table_kinds();
+            let rows = self.table_rows();
+            events.extend(self.alerts.iter_mut().filter_map(|alert| alert.check(&hdrs, &kinds, &rows)));
+        }
+        events
+    }
+
+    /// Returns the footer computed by the last update, over the rows which
+    /// passed the filter.
+    pub fn get_footer(&self) -> Vec<FooterCell> {
+        self.footer.clone()
+    }
+
+    fn compute_footer(&mut self) {
+        if self.aggregates.is_empty() {
+            self.footer.clear();
+            return;
+        }
+        let hdrs = self.table_headers();
+        let rows: Vec<Vec<String>> = self.filtered_rows().into_iter().map(|(_, row)| row).collect();
+        self.footer = self.aggregates.iter()
+            .map(|a| a.compute(&hdrs, &self.kinds, &rows))
+            .collect();
+    }
+
+    /// Appends the numeric cells of the output at hand to their history, once
+    /// per run.
+    fn record_history(&mut self) {
+        if self.recorded == Some(self.sampled) {
+            return;
+        }
+        self.recorded = Some(self.sampled);
+        let time = self.sampled;
+        let hdrs = self.table_headers();
+        let kinds = self.table_kinds();
+        let rows = self.table_rows();
+        let mut keys = HashSet::new();
+        for (key, row) in rows {
+            for (col, cell) in row.iter().enumerate() {
+                let kind = kinds.get(col).copied().unwrap_or(CellKind::Text);
+                if let (Some(column), Some(v)) = (hdrs.get(col), value::number(cell, kind)) {
+                    self.history.record(&key, column, time, v);
+                }
+            }
+            keys.insert(key);
+        }
+        self.history.retain_keys(&keys);
+    }
+
+    /// Sets how long a run may take before the command is killed.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Sets how many values are kept for each numeric cell.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Rebuilds the table rows from the latest output and returns the
+    /// positions that differ from what was previously sent.
+    pub fn update_view(&mut self) -> Vec<usize> {
+        self.kinds = self.table_kinds();
+        let kinds = self.kinds.clone();
+        self.view_kinds = self.projection().iter()
+            .map(|&col| kinds.get(col).copied().unwrap_or(CellKind::Text))
+            .collect();
+        self.record_history();
+        self.compute_footer();
+        let (keys, rows): (Vec<String>, Vec<Vec<String>>) = self.build_rows().into_iter().unzip();
+        let changed = diff(&self.view, &rows);
+        self.view = rows;
+        self.view_keys = keys;
+        changed
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.view.len()
+    }
+
+    /// Returns a table row along with the normalized numeric value of each cell.
+    pub fn get_row(&self, num: usize) -> Option<(Vec<String>, Vec<Option<f64>>)> {
+        let row = self.view.get(num)?.clone();
+        let numbers = value::numbers(&row, &self.view_kinds);
+        Some((row, numbers))
+    }
+
+    /// Returns the rows of the view, filtered, sorted and projected.
+    pub fn get_view_rows(&self) -> Vec<Vec<String>> {
+        self.view.clone()
+    }
+
+    pub fn get_row_key(&self, num: usize) -> Option<String> {
+        self.view_keys.get(num).cloned()
+    }
+
+    /// Returns the history of the numeric cells shown, by row key and
+    /// column name.
+    pub fn get_cell_history(&mut self) -> PointsByRow {
+        let shown = self.shown_columns();
+        self.history.by_key(&shown)
+    }
+
+    /// Returns when the latest output was captured and the value each
+    /// numeric cell shown took in it, by row key and column name.
+    pub fn get_latest_cells(&mut self) -> (f64, ValuesByRow) {
+        let shown = self.shown_columns();
+        (self.sampled, self.history.at(self.sampled, &shown))
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.history.limit()
+    }
+
+    fn shown_columns(&mut self) -> Vec<String> {
+        let hdrs = self.table_headers();
+        self.projection().into_iter().map(|col| hdrs[col].clone()).collect()
+    }
+
+    /// Returns the values a column took in the kept runs for the given row
+    /// keys, one series of (timestamp, value) points per key. A rate column
+    /// is derived from the counter values of consecutive runs.
+    pub fn query_history(&mut self, keys: &[String], column: &str, from: Option<f64>, to: Option<f64>)
+        -> Result<SeriesByKey, String> {
+        let runs: Vec<Run> = self.runs.range(from, to).into_iter().cloned().collect();
+        self.column_series(&runs, column, Some(keys))
+    }
+
+    /// Same as query_history over the given runs, for the keys given or for
+    /// every row found in the runs, in the order they appear.
+    fn column_series(&mut self, runs: &[Run], column: &str, keys: Option<&[String]>)
+        -> Result<SeriesByKey, String> {
+        let hdrs = self.get_headers().map_err(|e| e.to_string())?;
+        let kinds = self.get_kinds();
+        let (source, rate) = match column.strip_suffix("/s") {
+            Some(base) if hdrs.iter().any(|h| h == base) => (base, true),
+            _ => (column, false),
+        };
+        let col = hdrs.iter().position(|h| h == source)
+            .ok_or_else(|| format!("No column named {}", column))?;
+        let kind = kinds.get(col).copied().unwrap_or(CellKind::Text);
+        if kind == CellKind::Text {
+            return Err(format!("Column {} is not numeric", column));
+        }
+
+        let mut series: SeriesByKey = keys.unwrap_or_default().iter()
+            .map(|k| (k.clone(), Vec::new()))
+            .collect();
+        // Position of the series of each key, for the keys found in the runs
+        let mut known: HashMap<String, usize> = series.iter().enumerate()
+            .map(|(i, (k, _))| (k.clone(), i))
+            .collect();
+        for run in runs {
+            // Runs whose header differs cannot be lined up with this one
+            if run.lines.first().map(|l| l.split_whitespace().eq(hdrs.iter().map(String::as_str))) != Some(true) {
+                continue;
+            }
+            let rows = split_lines(&run.lines[1..]);
+            let row_keys = self.row_keys(&rows);
+            // The first row with a key wins, like the table shows it
+            let mut by_key: HashMap<&str, usize> = HashMap::new();
+            for (i, key) in row_keys.iter().enumerate() {
+                by_key.entry(key.as_str()).or_insert(i);
+                if keys.is_none() && !known.contains_key(key) {
+                    known.insert(key.clone(), series.len());
+                    series.push((key.clone(), Vec::new()));
+                }
+            }
+            for (key, points) in series.iter_mut() {
+                let v = by_key.get(key.as_str())
+                    .and_then(|&i| rows[i].get(col))
+                    .and_then(|cell| value::number(cell, kind));
+                if let Some(v) = v {
+                    points.push((run.timestamp, v));
+                }
+            }
+        }
+
+        if rate {
+            for (_, points) in series.iter_mut() {
+                *points = points.windows(2)
+                    .filter(|w| w[1].0 > w[0].0 && w[1].1 >= w[0].1)
+                    .map(|w| (w[1].0, (w[1].1 - w[0].1) / (w[1].0 - w[0].0)))
+                    .collect();
+            }
+        }
+        Ok(series)
+    }
+
+    /// Returns every run taken between two times, from the store when there
+    /// is one, from the recent runs otherwise.
+    pub fn stored_runs(&self, from: Option<f64>, to: Option<f64>) -> Result<Vec<Run>, String> {
+        match self.store {
+            Some(ref store) => store.range(from, to).map_err(|e| e.to_string()),
+            None => Ok(self.runs.range(from, to).into_iter().cloned().collect()),
+        }
+    }
+
+    /// Returns the values of some numeric columns in the given runs, one
+    /// point per run, row and column, oldest first.
+    pub fn history_points(&mut self, runs: &[Run], columns: &[String]) -> Result<Vec<HistoryPoint>, String> {
+        let mut points = Vec::new();
+        for column in columns {
+            for (row_key, series) in self.column_series(runs, column, None)? {
+                points.extend(series.into_iter().map(|(timestamp, value)| HistoryPoint {
+                    timestamp,
+                    row_key: row_key.clone(),
+                    column: column.clone(),
+                    value,
+                }));
+            }
+        }
+        points.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap_or(Ordering::Equal));
+        Ok(points)
+    }
+
+    /// Returns a run, from memory when it is recent enough, from the store
+    /// otherwise.
+    fn load_run(&self, id: u64) -> Result<Run, String> {
+        if let Some(run) = self.runs.get(id) {
+            return Ok(run.clone());
+        }
+        let stored = match self.store {
+            Some(ref store) => store.get(id).map_err(|e| e.to_string())?,
+            None => None,
+        };
+        stored.ok_or_else(|| format!("Run {} is no longer kept", id))
+    }
+
+    /// The run last executed or pushed, failed or not.
+    pub fn latest_run(&self) -> Option<&Run> {
+        self.runs.latest()
+    }
+
+    /// Returns the ids of the oldest and newest runs which can be looked at.
+    pub fn get_run_range(&self) -> Option<(u64, u64)> {
+        let last = self.runs.last_id()?;
+        let first = self.store.as_ref()
+            .and_then(|store| store.first_id())
+            .or_else(|| self.runs.first_id())?;
+        Some((first, last))
+    }
+
+    /// Splits a run into headers and rows, keying the rows like the live table.
+    fn run_table(&self, run: &Run) -> RunTable {
+        let table = split_lines(&run.lines);
+        let headers = table.first().cloned().unwrap_or_default();
+        let rows = table.get(1..).unwrap_or_default().to_vec();
+        let keys = self.row_keys_for(&headers, &rows);
+        RunTable { headers, keys, rows }
+    }
+
+    /// Returns a past run with what changed since the run before it. Rows are
+    /// matched by key, a row not in the previous run has all its cells changed.
+    pub fn get_snapshot(&self, id: u64) -> Result<Snapshot, String> {
+        let run = self.load_run(id)?;
+        let prev = if id > 1 { self.load_run(id - 1).ok() } else { None };
+        let table = self.run_table(&run);
+
+        let mut changed = vec![Vec::new(); table.rows.len()];
+        if let Some(ref prev) = prev {
+            let rows = diff_tables(&self.run_table(prev), &table);
+            for i in rows.added {
+                changed[i] = (0..table.rows[i].len()).collect();
+            }
+            for (_, i, cells) in rows.changed {
+                changed[i] = cells;
+            }
+        }
+
+        Ok(Snapshot {
+            run_id: run.id,
+            timestamp: run.timestamp,
+            duration: run.duration,
+            status: run.status,
+            changed_lines: prev.map(|p| diff(&p.lines, &run.lines)).unwrap_or_default(),
+            lines: run.lines,
+            stderr: run.stderr,
+            headers: table.headers,
+            keys: table.keys,
+            rows: table.rows,
+            changed,
+        })
+    }
+
+    /// Differences the latest run brought, compared to the successful run
+    /// before it. None when there is only one, or the latest one failed.
+    pub fn latest_diff(&self) -> Option<RunDiff> {
+        if self.runs.latest().is_none_or(|run| run.status != Some(0)) {
+            return None;
+        }
+        let (from, to) = self.runs.last_successes()?;
+        self.get_run_diff(from, to).ok()
+    }
+
+    /// Compares two runs, which need not be consecutive: rows only in the
+    /// later one, rows only in the earlier one, and rows whose cells differ.
+    pub fn get_run_diff(&self, from: u64, to: u64) -> Result<RunDiff, String> {
+        let old_run = self.load_run(from)?;
+        let new_run = self.load_run(to)?;
+        let old = self.run_table(&old_run);
+        let new = self.run_table(&new_run);
+        let rows = diff_tables(&old, &new);
+
+        let row = |table: &RunTable, i: usize| DiffRow { key: table.keys[i].clone(), values: table.rows[i].clone() };
+        Ok(RunDiff {
+            from,
+            to,
+            from_timestamp: old_run.timestamp,
+            to_timestamp: new_run.timestamp,
+            added: rows.added.iter().map(|&i| row(&new, i)).collect(),
+            removed: rows.removed.iter().map(|&i| row(&old, i)).collect(),
+            changed: rows.changed.into_iter()
+                .map(|(o, n, cells)| ChangedRow {
+                    key: new.keys[n].clone(),
+                    old: old.rows[o].clone(),
+                    new: new.rows[n].clone(),
+                    cells,
+                })
+                .collect(),
+            old_headers: old.headers,
+            headers: new.headers,
+        })
+    }
+
+    /// Returns the whole output, one entry per line, with whitespace preserved.
+    pub fn get_text(&self) -> Vec<String> {
+        self.output.clone()
+    }
+
+    pub fn update_lines(&mut self, oplines: Vec<String>) -> Result<Vec<usize>, String> {
+        let changed = diff(&self.output, &oplines);
+        for &oi in changed.iter().filter(|&&oi| oi < self.output.len().min(oplines.len())) {
+            println!("Found a new update on line numbered {} is {}", oi, oplines[oi]);
+            println!("Found a old update on line numbered {} is {}", oi, self.output[oi]);
+        }
+
+        self.output = oplines;
+        // Replayed runs keep the time they were recorded at
+        self.sampled = self.runs.latest().map_or_else(now_secs, |run| run.timestamp);
         self.current = 0;
+        self.update_rates();
 
         Ok(changed)
     }
@@ -189,3 +1305,170 @@ impl CmdOutput {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(timestamp: f64, lines: &[&str]) -> Run {
+        Run {
+            id: 0,
+            timestamp,
+            duration: 0.1,
+            status: Some(0),
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// Adds a run as a replay does and returns the rows shown.
+    fn step(colines: &mut CmdOutput, timestamp: f64, lines: &[&str]) -> Vec<Vec<String>> {
+        let lines = colines.push_run(run(timestamp, lines)).unwrap();
+        colines.update_lines(lines).unwrap();
+        colines.update_view();
+        colines.get_view_rows()
+    }
+
+    /// Shows the rate of the BYTES counter from one value to the next, ten
+    /// seconds later.
+    fn rate(from: &str, to: &str) -> String {
+        let mut colines = CmdOutput::from_run("ifstat", run(100.0, &["IFACE BYTES", &format!("eth0 {}", from)]));
+        colines.set_rates(vec!["BYTES".to_string()]).unwrap();
+        assert_eq!(colines.table_headers(), vec!["IFACE", "BYTES", "BYTES/s"]);
+        let rows = step(&mut colines, 110.0, &["IFACE BYTES", &format!("eth0 {}", to)]);
+        rows[0][2].clone()
+    }
+
+    #[test]
+    fn rate_of_a_counter() {
+        assert_eq!(rate("1000", "1500"), "50.0");
+        assert_eq!(rate("1000", "1000"), "0.0");
+    }
+
+    #[test]
+    fn counter_reset_has_no_rate() {
+        assert_eq!(rate("5000", "10"), "-");
+        // Nor does a 64 bit counter going back to zero
+        assert_eq!(rate("10000000000", "0"), "-");
+    }
+
+    #[test]
+    fn counter_wrapping_around_32_bits() {
+        // 296 up to 2^32, 704 past it
+        assert_eq!(rate("4294967000", "704"), "100.0");
+    }
+
+    #[test]
+    fn counter_wrapping_around_64_bits() {
+        // 4096 up to 2^64, values f64 holds exactly
+        assert_eq!(rate("18446744073709547520", "4096"), "819.2");
+    }
+
+    #[test]
+    fn rate_comes_back_after_a_reset() {
+        let mut colines = CmdOutput::from_run("ifstat", run(100.0, &["IFACE BYTES", "eth0 5000"]));
+        colines.set_rates(vec!["BYTES".to_string()]).unwrap();
+        assert_eq!(step(&mut colines, 110.0, &["IFACE BYTES", "eth0 10"])[0][2], "-");
+        assert_eq!(step(&mut colines, 120.0, &["IFACE BYTES", "eth0 210"])[0][2], "20.0");
+    }
+
+    /// Runs 1 to 3 of a ps like command, as kept by the watch.
+    fn ps_runs() -> CmdOutput {
+        let mut colines = CmdOutput::from_run("ps", run(100.0, &["PID CMD CPU", "1 init 0", "2 sshd 5"]));
+        colines.push_run(run(110.0, &["PID CMD CPU", "1 init 0", "2 sshd 7", "3 bash 1"])).unwrap();
+        colines.push_run(run(120.0, &["PID CMD CPU", "1 init 0", "3 bash 2"])).unwrap();
+        colines
+    }
+
+    #[test]
+    fn snapshot_marks_cells_changed_since_the_run_before() {
+        let colines = ps_runs();
+        let first = colines.get_snapshot(1).unwrap();
+        assert!(first.changed.iter().all(Vec::is_empty));
+        assert!(first.changed_lines.is_empty());
+
+        let second = colines.get_snapshot(2).unwrap();
+        assert_eq!(second.keys, vec!["1", "2", "3"]);
+        // A cell which changed, and a row which is new
+        assert_eq!(second.changed, vec![vec![], vec![2], vec![0, 1, 2]]);
+        assert_eq!(second.changed_lines, vec![2, 3]);
+
+        assert!(colines.get_snapshot(4).is_err());
+    }
+
+    #[test]
+    fn diff_of_runs_far_apart() {
+        let colines = ps_runs();
+        let diff = colines.get_run_diff(1, 3).unwrap();
+        let keys = |rows: &[DiffRow]| rows.iter().map(|row| row.key.clone()).collect::<Vec<String>>();
+        assert_eq!(keys(&diff.added), vec!["3"]);
+        assert_eq!(keys(&diff.removed), vec!["2"]);
+        assert!(diff.changed.is_empty());
+
+        let diff = colines.get_run_diff(2, 3).unwrap();
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key, "3");
+        assert_eq!(diff.changed[0].cells, vec![2]);
+        assert_eq!(diff.changed[0].old[2], "1");
+        assert_eq!(diff.changed[0].new[2], "2");
+    }
+
+    #[test]
+    fn diff_across_a_header_change_changes_every_cell() {
+        let mut colines = CmdOutput::from_run("ps", run(100.0, &["PID CPU", "1 0"]));
+        colines.push_run(run(110.0, &["PID MEM", "1 0"])).unwrap();
+        let diff = colines.get_run_diff(1, 2).unwrap();
+        assert_eq!(diff.old_headers, vec!["PID", "CPU"]);
+        assert_eq!(diff.headers, vec!["PID", "MEM"]);
+        assert_eq!(diff.changed[0].cells, vec![0, 1]);
+    }
+
+    #[test]
+    fn latest_diff_skips_failed_runs() {
+        let mut colines = ps_runs();
+        let mut failed = run(130.0, &["PID CMD CPU"]);
+        failed.status = Some(1);
+        assert!(colines.push_run(failed).is_err());
+        assert!(colines.latest_diff().is_none());
+        colines.push_run(run(140.0, &["PID CMD CPU", "1 init 1"])).unwrap();
+        let diff = colines.latest_diff().unwrap();
+        assert_eq!((diff.from, diff.to), (3, 5));
+    }
+
+    #[test]
+    fn history_points_of_every_row_oldest_first() {
+        let mut colines = ps_runs();
+        let runs = colines.stored_runs(None, None).unwrap();
+        let points = colines.history_points(&runs, &["CPU".to_string()]).unwrap();
+        let seen: Vec<(f64, &str, f64)> = points.iter()
+            .map(|p| (p.timestamp, p.row_key.as_str(), p.value))
+            .collect();
+        assert_eq!(seen, vec![
+            (100.0, "1", 0.0), (100.0, "2", 5.0),
+            (110.0, "1", 0.0), (110.0, "2", 7.0), (110.0, "3", 1.0),
+            (120.0, "1", 0.0), (120.0, "3", 2.0),
+        ]);
+        assert!(points.iter().all(|p| p.column == "CPU"));
+
+        let recent = colines.stored_runs(Some(115.0), None).unwrap();
+        assert_eq!(colines.history_points(&recent, &["CPU".to_string()]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn history_points_skip_what_cannot_be_lined_up() {
+        let mut colines = CmdOutput::from_run("ps", run(100.0, &["PID CPU", "1 10", "2 -"]));
+        colines.push_run(run(110.0, &["PID MEM CPU", "1 5 20"])).unwrap();
+        colines.push_run(run(120.0, &["PID CPU", "1 40", "2 5"])).unwrap();
+        let runs = colines.stored_runs(None, None).unwrap();
+        let points = colines.history_points(&runs, &["CPU".to_string()]).unwrap();
+        let seen: Vec<(f64, &str)> = points.iter().map(|p| (p.timestamp, p.row_key.as_str())).collect();
+        assert_eq!(seen, vec![(100.0, "1"), (120.0, "1"), (120.0, "2")]);
+
+        // Rates between the runs kept, per second
+        let rates = colines.history_points(&runs, &["CPU/s".to_string()]).unwrap();
+        assert_eq!(rates.len(), 1);
+        assert_eq!((rates[0].timestamp, rates[0].value), (120.0, 1.5));
+
+        assert!(colines.history_points(&runs, &["MEM".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use serde::{Serialize, Deserialize};
+use crate::cmd::HistoryPoint;
+
+/// Formats the table can be exported to.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn parse(text: &str) -> Result<ExportFormat, String> {
+        match text.trim().to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "json" => Ok(ExportFormat::Jsonl),
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "html" => Ok(ExportFormat::Html),
+            other => Err(format!("Unknown export format {}, use csv, jsonl, markdown or html", other)),
+        }
+    }
+
+    /// Usual file name extension.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Writes a table with its headers in the given format. The title is the
+/// command line, used where the format has room for it.
+pub fn render(format: ExportFormat, title: &str, headers: &[String], rows: &[Vec<String>]) -> String {
+    match format {
+        ExportFormat::Csv => csv(headers, rows),
+        ExportFormat::Jsonl => jsonl(headers, rows),
+        ExportFormat::Markdown => markdown(headers, rows),
+        ExportFormat::Html => html(title, headers, rows),
+    }
+}
+
+/// Writes history in the long format, one `timestamp, row_key, column,
+/// value` record per line, which notebooks and spreadsheets pivot easily.
+/// Only CSV and JSON lines suit it.
+pub fn render_history(format: ExportFormat, points: &[HistoryPoint]) -> Result<String, String> {
+    let time = |secs: f64| chrono::DateTime::from_timestamp_millis((secs * 1000.0).round() as i64)
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_default();
+    match format {
+        ExportFormat::Csv => {
+            let headers: Vec<String> = ["timestamp", "row_key", "column", "value"].iter().map(|h| h.to_string()).collect();
+            let rows: Vec<Vec<String>> = points.iter()
+                .map(|p| vec![time(p.timestamp), p.row_key.clone(), p.column.clone(), p.value.to_string()])
+                .collect();
+            Ok(csv(&headers, &rows))
+        },
+        ExportFormat::Jsonl => {
+            // Typed fields, so that the value stays a number
+            #[derive(Serialize)]
+            struct Record<'a> {
+                timestamp: String,
+                row_key: &'a str,
+                column: &'a str,
+                value: f64,
+            }
+            let mut out = String::new();
+            for p in points {
+                let record = Record { timestamp: time(p.timestamp), row_key: &p.row_key, column: &p.column, value: p.value };
+                out.push_str(&serde_json::to_string(&record).map_err(|e| e.to_string())?);
+                out.push('\n');
+            }
+            Ok(out)
+        },
+        _ => Err("History can only be exported as csv or jsonl".to_string()),
+    }
+}
+
+/// RFC 4180 CSV: fields with commas, quotes or line breaks are quoted.
+fn csv(headers: &[String], rows: &[Vec<String>]) -> String {
+    fn field(text: &str) -> String {
+        if text.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    }
+    let mut out = String::new();
+    for row in std::iter::once(headers).chain(rows.iter().map(Vec::as_slice)) {
+        out.push_str(&row.iter().map(|cell| field(cell)).collect::<Vec<_>>().join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// One JSON object per row, keyed by header, keeping the column order.
+fn jsonl(headers: &[String], rows: &[Vec<String>]) -> String {
+    let quote = |text: &str| serde_json::to_string(text).unwrap_or_default();
+    let mut out = String::new();
+    for row in rows {
+        let fields: Vec<String> = headers.iter().enumerate()
+            .map(|(i, h)| format!("{}:{}", quote(h), quote(row.get(i).map(String::as_str).unwrap_or(""))))
+            .collect();
+        out.push('{');
+        out.push_str(&fields.join(","));
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn markdown(headers: &[String], rows: &[Vec<String>]) -> String {
+    fn cell(text: &str) -> String {
+        text.replace('\\', "\\\\").replace('|', "\\|")
+    }
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut out = line(headers.iter().map(|h| cell(h)).collect());
+    out.push_str(&line(headers.iter().map(|_| "---".to_string()).collect()));
+    for row in rows {
+        out.push_str(&line((0..headers.len()).map(|i| cell(row.get(i).map(String::as_str).unwrap_or(""))).collect()));
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A standalone page, readable without anything else.
+fn html(title: &str, headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    out.push_str("<style>\n\
+        body { font-family: sans-serif; }\n\
+        table { border-collapse: collapse; }\n\
+        th, td { border: 1px solid #ccc; padding: 4px 8px; }\n\
+        th { background: #f2f2f2; }\n\
+        </style>\n</head>\n<body>\n");
+    out.push_str(&format!("<h1>{}</h1>\n<p>{}</p>\n",
+        escape_html(title), escape_html(&chrono::Local::now().to_rfc2822())));
+    out.push_str("<table>\n<thead>\n<tr>");
+    for h in headers {
+        out.push_str(&format!("<th>{}</th>", escape_html(h)));
+    }
+    out.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = vec!["NAME".to_string(), "NOTE".to_string()];
+        let rows = vec![
+            vec!["a".to_string(), "plain".to_string()],
+            vec!["b".to_string(), "one, \"two\"\nthree".to_string()],
+        ];
+        (headers, rows)
+    }
+
+    #[test]
+    fn csv_quotes_what_needs_it() {
+        let (headers, rows) = table();
+        assert_eq!(render(ExportFormat::Csv, "cmd", &headers, &rows),
+            "NAME,NOTE\r\na,plain\r\nb,\"one, \"\"two\"\"\nthree\"\r\n");
+    }
+
+    #[test]
+    fn jsonl_escapes_cells() {
+        let (headers, rows) = table();
+        let text = render(ExportFormat::Jsonl, "cmd", &headers, &rows);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines, vec![
+            r#"{"NAME":"a","NOTE":"plain"}"#,
+            r#"{"NAME":"b","NOTE":"one, \"two\"\nthree"}"#,
+        ]);
+        let row: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(row["NOTE"], "one, \"two\"\nthree");
+    }
+}
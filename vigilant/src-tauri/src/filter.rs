@@ -0,0 +1,375 @@
+use regex::Regex;
+use crate::value::{self, CellKind};
+
+/// Comparison operators usable in a filter expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches,
+    NotMatches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Regex(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        column: String,
+        op: Op,
+        value: String,
+        regex: Option<Regex>,
+    },
+    Contains(String), // Substring anywhere in the row
+    Matches(Regex),   // Regex anywhere in the row
+}
+
+/// A row filter, either an expression over the columns such as
+/// `STATE == "ESTAB" && PORT > 1024`, a `/regex/`, or a plain substring.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    text: String,
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parses a filter. Text without any operator is taken as a substring.
+    pub fn parse(text: &str) -> Result<Filter, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Empty filter".to_string());
+        }
+        let is_regex = text.len() > 1 && text.starts_with('/') && text.ends_with('/');
+        let expr = if !is_regex && !text.contains(|c| "=<>!&|()\"'".contains(c)) {
+            Expr::Contains(text.to_string())
+        } else {
+            let tokens = tokenize(text)?;
+            let mut parser = Parser { tokens, pos: 0 };
+            let expr = parser.parse_or()?;
+            if let Some(tok) = parser.tokens.get(parser.pos) {
+                return Err(format!("Unexpected {:?} in filter", tok));
+            }
+            expr
+        };
+        Ok(Filter { text: text.to_string(), expr })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Makes sure every column the filter refers to exists.
+    pub fn check_columns(&self, headers: &[String]) -> Result<(), String> {
+        fn walk(expr: &Expr, headers: &[String]) -> Result<(), String> {
+            match expr {
+                Expr::And(a, b) | Expr::Or(a, b) => {
+                    walk(a, headers)?;
+                    walk(b, headers)
+                },
+                Expr::Not(a) => walk(a, headers),
+                Expr::Compare { column, .. } if !headers.contains(column) => {
+                    Err(format!("No column named {}", column))
+                },
+                _ => Ok(()),
+            }
+        }
+        walk(&self.expr, headers)
+    }
+
+    /// Returns true when the row passes the filter. Comparisons use the typed
+    /// value of the cell when both sides are numeric, text otherwise.
+    pub fn matches(&self, headers: &[String], kinds: &[CellKind], row: &[String]) -> bool {
+        eval(&self.expr, headers, kinds, row)
+    }
+}
+
+fn eval(expr: &Expr, headers: &[String], kinds: &[CellKind], row: &[String]) -> bool {
+    match expr {
+        Expr::And(a, b) => eval(a, headers, kinds, row) && eval(b, headers, kinds, row),
+        Expr::Or(a, b) => eval(a, headers, kinds, row) || eval(b, headers, kinds, row),
+        Expr::Not(a) => !eval(a, headers, kinds, row),
+        Expr::Contains(s) => row.join(" ").contains(s.as_str()),
+        Expr::Matches(re) => re.is_match(&row.join(" ")),
+        Expr::Compare { column, op, value, regex } => {
+            let col = match headers.iter().position(|h| h == column) {
+                Some(col) => col,
+                None => return false,
+            };
+            let cell = row.get(col).map(String::as_str).unwrap_or("");
+            if let Some(re) = regex {
+                return re.is_match(cell) == (*op == Op::Matches);
+            }
+
+            let kind = kinds.get(col).copied().unwrap_or(CellKind::Text);
+            let ord = match (value::number(cell, kind), value::parse_cell(value).1) {
+                (Some(a), Some(b)) if kind != CellKind::Text => a.partial_cmp(&b),
+                _ => Some(cell.cmp(value.as_str())),
+            };
+            let ord = match ord {
+                Some(ord) => ord,
+                None => return false, // NaN never matches
+            };
+            match op {
+                Op::Eq => ord.is_eq(),
+                Op::Ne => ord.is_ne(),
+                Op::Lt => ord.is_lt(),
+                Op::Le => ord.is_le(),
+                Op::Gt => ord.is_gt(),
+                Op::Ge => ord.is_ge(),
+                Op::Matches | Op::NotMatches => false,
+            }
+        },
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // A slash only opens a regex closed at the end of the token, paths
+        // such as /usr/bin are plain words
+        if c == '/' {
+            if let Some((s, len)) = quoted(&chars, i) {
+                if chars.get(i + len).is_none_or(|d| d.is_whitespace() || "()&|".contains(*d)) {
+                    tokens.push(Token::Regex(s));
+                    i += len;
+                    continue;
+                }
+            }
+        }
+        let (tok, len) = match (c, next) {
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('=', Some('~')) => (Token::Op(Op::Matches), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('!', Some('~')) => (Token::Op(Op::NotMatches), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('"', _) | ('\'', _) => match quoted(&chars, i) {
+                Some((s, len)) => (Token::Str(s), len),
+                None => return Err(format!("Unterminated {} in filter", c)),
+            },
+            _ => {
+                let mut j = i;
+                while j < chars.len() && !chars[j].is_whitespace() && !"()<>=!&|\"'".contains(chars[j]) {
+                    j += 1;
+                }
+                if j == i {
+                    return Err(format!("Unexpected {} in filter", c));
+                }
+                (Token::Word(chars[i..j].iter().collect()), j - i)
+            },
+        };
+        tokens.push(tok);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// Reads the text delimited by the character at `start`, a backslash escaping
+/// the delimiter. Returns the text and the length taken with both delimiters.
+fn quoted(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let delim = chars[start];
+    let mut s = String::new();
+    let mut j = start + 1;
+    loop {
+        match chars.get(j) {
+            None => return None,
+            Some(&d) if d == delim => break,
+            Some('\\') if chars.get(j + 1) == Some(&delim) => {
+                s.push(delim);
+                j += 2;
+            },
+            Some(&d) => {
+                s.push(d);
+                j += 1;
+            },
+        }
+    }
+    Some((s, j + 1 - start))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn take(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.parse_unary()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.take() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.take() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err("Missing ) in filter".to_string()),
+                }
+            },
+            Some(Token::Str(s)) => Ok(Expr::Contains(s)),
+            Some(Token::Regex(r)) => Ok(Expr::Matches(compile(&r)?)),
+            Some(Token::Word(column)) => {
+                let op = match self.take() {
+                    Some(Token::Op(op)) => op,
+                    _ => return Err(format!("Expected a comparison after {}", column)),
+                };
+                let value = match self.take() {
+                    Some(Token::Word(v)) | Some(Token::Str(v)) | Some(Token::Regex(v)) => v,
+                    _ => return Err(format!("Expected a value after {} {:?}", column, op)),
+                };
+                let regex = match op {
+                    Op::Matches | Op::NotMatches => Some(compile(&value)?),
+                    _ => None,
+                };
+                Ok(Expr::Compare { column, op, value, regex })
+            },
+            Some(tok) => Err(format!("Unexpected {:?} in filter", tok)),
+            None => Err("Filter ends too early".to_string()),
+        }
+    }
+}
+
+fn compile(re: &str) -> Result<Regex, String> {
+    Regex::new(re).map_err(|e| format!("Bad regex {}: {}", re, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        ["USER", "PID", "CMD"].iter().map(|h| h.to_string()).collect()
+    }
+
+    fn passes(filter: &str, row: &[&str]) -> bool {
+        let row: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+        let kinds = [CellKind::Text, CellKind::Integer, CellKind::Text];
+        Filter::parse(filter).unwrap().matches(&headers(), &kinds, &row)
+    }
+
+    #[test]
+    fn comparisons_are_typed() {
+        assert!(passes("PID > 900", &["root", "1000", "init"]));
+        assert!(!passes("PID > 900", &["root", "95", "init"]));
+        assert!(passes("PID >= 95 && PID <= 95", &["root", "95", "init"]));
+        assert!(passes("USER != root", &["www", "1", "nginx"]));
+        assert!(passes("USER < www", &["root", "1", "init"]));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // Read as USER == root || (PID == 2 && CMD == x)
+        assert!(passes("USER == root || PID == 2 && CMD == x", &["root", "1", "init"]));
+        assert!(!passes("(USER == root || PID == 2) && CMD == x", &["root", "1", "init"]));
+        assert!(!passes("!USER == root || PID == 2", &["root", "1", "init"]));
+        assert!(passes("!(USER == root && PID == 2)", &["root", "1", "init"]));
+    }
+
+    #[test]
+    fn quoting() {
+        assert!(passes("CMD == \"my app\"", &["root", "1", "my app"]));
+        assert!(passes("CMD == 'a && b'", &["root", "1", "a && b"]));
+        assert!(passes("CMD == \"say \\\"hi\\\"\"", &["root", "1", "say \"hi\""]));
+        assert!(passes("\"y ap\" || PID == 2", &["root", "1", "my app"]));
+    }
+
+    #[test]
+    fn regexes_and_paths() {
+        assert!(passes("/^www .*x$/", &["www", "1", "nginx"]));
+        assert!(!passes("/^www .*x$/", &["www", "1", "apache"]));
+        assert!(passes("CMD =~ /^ngi/", &["www", "1", "nginx"]));
+        assert!(passes("CMD !~ '^ngi'", &["www", "1", "apache"]));
+        assert!(passes("/www/ && PID < 2", &["www", "1", "nginx"]));
+
+        // Paths are substrings or plain values, not regexes
+        assert!(passes("/usr/bin", &["root", "1", "/usr/bin/ls"]));
+        assert!(!passes("/usr/bin", &["root", "1", "/bin/ls"]));
+        assert!(passes("CMD == /usr/bin/ls", &["root", "1", "/usr/bin/ls"]));
+        assert!(passes("CMD == /usr/bin/ls || PID == 2", &["root", "1", "/usr/bin/ls"]));
+    }
+
+    #[test]
+    fn substrings() {
+        assert!(passes("root", &["root", "1", "init"]));
+        assert!(passes("root 1", &["root", "1", "init"]));
+        assert!(!passes("nobody", &["root", "1", "init"]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Filter::parse("  ").unwrap_err(), "Empty filter");
+        assert_eq!(Filter::parse("CMD == \"abc").unwrap_err(), "Unterminated \" in filter");
+        assert_eq!(Filter::parse("(PID > 1").unwrap_err(), "Missing ) in filter");
+        assert_eq!(Filter::parse("PID > 1)").unwrap_err(), "Unexpected RParen in filter");
+        assert_eq!(Filter::parse("PID 1 == 2").unwrap_err(), "Expected a comparison after PID");
+        assert_eq!(Filter::parse("PID >").unwrap_err(), "Expected a value after PID Gt");
+        assert_eq!(Filter::parse("PID > 1 &&").unwrap_err(), "Filter ends too early");
+        assert!(Filter::parse("CMD =~ /(/").unwrap_err().starts_with("Bad regex ("));
+    }
+
+    #[test]
+    fn unknown_columns() {
+        let filter = Filter::parse("NAME == x || PID > 1").unwrap();
+        assert_eq!(filter.check_columns(&headers()).unwrap_err(), "No column named NAME");
+        assert!(Filter::parse("PID > 1").unwrap().check_columns(&headers()).is_ok());
+    }
+}
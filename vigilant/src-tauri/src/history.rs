@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Serialize, Deserialize};
+
+/// Points of cells by row key and column name, oldest first.
+pub type PointsByRow = HashMap<String, HashMap<String, Vec<(f64, f64)>>>;
+
+/// One value of cells by row key and column name.
+pub type ValuesByRow = HashMap<String, HashMap<String, f64>>;
+
+/// Points of a column for each row key, in the order the rows were asked
+/// for or found.
+pub type SeriesByKey = Vec<(String, Vec<(f64, f64)>)>;
+
+/// Bounded time series of the numeric value of every cell, keyed by row key
+/// and column name. Points are (seconds since the epoch, value).
+#[derive(Debug, Clone)]
+pub struct CellHistory {
+    limit: usize,
+    series: HashMap<(String, String), VecDeque<(f64, f64)>>,
+}
+
+impl CellHistory {
+    pub fn new(limit: usize) -> Self {
+        CellHistory {
+            limit: limit.max(1),
+            series: HashMap::new(),
+        }
+    }
+
+    /// Changes how many points are kept per cell, dropping the oldest ones.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        for points in self.series.values_mut() {
+            while points.len() > self.limit {
+                points.pop_front();
+            }
+        }
+    }
+
+    pub fn record(&mut self, key: &str, column: &str, time: f64, value: f64) {
+        let points = self.series.entry((key.to_string(), column.to_string())).or_default();
+        points.push_back((time, value));
+        if points.len() > self.limit {
+            points.pop_front();
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the points kept for the given columns, by row key and column.
+    pub fn by_key(&self, columns: &[String]) -> PointsByRow {
+        let mut rows = PointsByRow::new();
+        for ((key, column), points) in &self.series {
+            if columns.contains(column) && !points.is_empty() {
+                rows.entry(key.clone()).or_default().insert(column.clone(), points.iter().copied().collect());
+            }
+        }
+        rows
+    }
+
+    /// Returns the values the given columns took at a time, by row key and
+    /// column, for the cells which have a point at that time.
+    pub fn at(&self, time: f64, columns: &[String]) -> ValuesByRow {
+        let mut rows = ValuesByRow::new();
+        for ((key, column), points) in &self.series {
+            if let Some(&(t, v)) = points.back() {
+                if t == time && columns.contains(column) {
+                    rows.entry(key.clone()).or_default().insert(column.clone(), v);
+                }
+            }
+        }
+        rows
+    }
+
+    /// Forgets the rows which are no longer in the output.
+    pub fn retain_keys(&mut self, keys: &HashSet<String>) {
+        self.series.retain(|(key, _), _| keys.contains(key));
+    }
+}
+
+/// Output of one run of the command.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Run {
+    pub id: u64,
+    pub timestamp: f64, // Seconds since the epoch
+    #[serde(default)]
+    pub duration: f64,  // Seconds the command took
+    #[serde(default)]
+    pub status: Option<i32>,  // Exit code, None when killed by a signal
+    pub lines: Vec<String>,   // Standard output
+    #[serde(default)]
+    pub stderr: Vec<String>,
+}
+
+/// The most recent runs of the command, oldest first.
+#[derive(Debug, Clone)]
+pub struct RunHistory {
+    limit: usize,
+    next_id: u64,
+    runs: VecDeque<Run>,
+}
+
+impl RunHistory {
+    pub fn new(limit: usize) -> Self {
+        RunHistory {
+            limit: limit.max(1),
+            next_id: 1,
+            runs: VecDeque::new(),
+        }
+    }
+
+    /// Adds a run, giving it the next id, and returns that id.
+    pub fn push(&mut self, mut run: Run) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        run.id = id;
+        self.runs.push_back(run);
+        if self.runs.len() > self.limit {
+            self.runs.pop_front();
+        }
+        id
+    }
+
+    /// Replaces the runs with ones loaded from the store, new runs are then
+    /// numbered after the last stored one. Returns the runs replaced.
+    pub fn resume(&mut self, runs: Vec<Run>, last_id: Option<u64>) -> Vec<Run> {
+        let replaced = self.runs.drain(..).collect();
+        self.runs.extend(runs);
+        while self.runs.len() > self.limit {
+            self.runs.pop_front();
+        }
+        self.next_id = last_id.map_or(1, |id| id + 1);
+        replaced
+    }
+
+    pub fn first_id(&self) -> Option<u64> {
+        self.runs.front().map(|run| run.id)
+    }
+
+    pub fn last_id(&self) -> Option<u64> {
+        self.runs.back().map(|run| run.id)
+    }
+
+    pub fn latest(&self) -> Option<&Run> {
+        self.runs.back()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Run> {
+        self.runs.iter().find(|run| run.id == id)
+    }
+
+    /// Ids of the two latest runs which succeeded, the older one first.
+    pub fn last_successes(&self) -> Option<(u64, u64)> {
+        let mut ok = self.runs.iter().rev().filter(|run| run.status == Some(0));
+        let last = ok.next()?.id;
+        let previous = ok.next()?.id;
+        Some((previous, last))
+    }
+
+    /// Returns the runs taken between two times, both optional and inclusive.
+    pub fn range(&self, from: Option<f64>, to: Option<f64>) -> Vec<&Run> {
+        self.runs.iter()
+            .filter(|run| from.is_none_or(|t| run.timestamp >= t))
+            .filter(|run| to.is_none_or(|t| run.timestamp <= t))
+            .collect()
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use serde::Serialize;
+use crate::alert::AlertRow;
+use crate::cmd::RunDiff;
+
+/// What a hook is told about on its standard input, as one JSON object.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum HookEvent {
+    /// The output of the command changed
+    Change {
+        lines: Vec<String>,      // Lines which changed, as they are now
+        diff: Option<RunDiff>,   // Rows added, removed and changed, for tables
+    },
+    /// An alert fired or resolved
+    Alert {
+        rule: String,
+        state: &'static str,     // fired or resolved
+        headers: Vec<String>,
+        rows: Vec<AlertRow>,
+    },
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::Change { .. } => "change",
+            HookEvent::Alert { .. } => "alert",
+        }
+    }
+}
+
+/// The run an event happened in.
+#[derive(Serialize, Debug)]
+pub struct HookRun {
+    pub cmdline: String,
+    pub run_id: Option<u64>,
+    pub timestamp: Option<f64>,
+    pub status: Option<i32>,     // Exit code, None when killed
+}
+
+#[derive(Serialize, Debug)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a HookEvent,
+    #[serde(flatten)]
+    run: &'a HookRun,
+}
+
+/// The JSON describing an event in full, as given to hooks and webhooks.
+pub fn payload(event: &HookEvent, run: &HookRun) -> serde_json::Result<String> {
+    serde_json::to_string(&Payload { event, run })
+}
+
+/// Runs a hook command through the shell without waiting for it. The event
+/// is described by `VIGILANT_*` environment variables and, in full, as
+/// JSON on standard input.
+pub fn spawn(command: &str, event: &HookEvent, run: &HookRun) {
+    let input = match payload(event, run) {
+        Ok(input) => input + "\n",
+        Err(e) => {
+            eprintln!("Cannot describe the event to {}: {}", command, e);
+            return;
+        },
+    };
+    let mut env = vec![
+        ("VIGILANT_EVENT", event.name().to_string()),
+        ("VIGILANT_CMDLINE", run.cmdline.clone()),
+        ("VIGILANT_RUN_ID", run.run_id.map(|id| id.to_string()).unwrap_or_default()),
+        ("VIGILANT_TIMESTAMP", run.timestamp.map(|t| t.to_string()).unwrap_or_default()),
+        ("VIGILANT_EXIT_CODE", run.status.map(|code| code.to_string()).unwrap_or_default()),
+    ];
+    match event {
+        HookEvent::Change { lines, diff } => {
+            env.push(("VIGILANT_CHANGED", match diff {
+                Some(diff) => diff.added.len() + diff.removed.len() + diff.changed.len(),
+                None => lines.len(),
+            }.to_string()));
+        },
+        HookEvent::Alert { rule, state, rows, .. } => {
+            env.push(("VIGILANT_ALERT", rule.clone()));
+            env.push(("VIGILANT_ALERT_STATE", state.to_string()));
+            env.push(("VIGILANT_ROWS", rows.len().to_string()));
+        },
+    }
+
+    let child = Command::new("sh").arg("-c").arg(command)
+        .envs(env)
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Cannot run hook {}: {}", command, e);
+            return;
+        },
+    };
+    // Waited for apart, a slow hook must not hold up the next run
+    let command = command.to_string();
+    thread::spawn(move || {
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook may well not read its input
+            let _ = stdin.write_all(input.as_bytes());
+        }
+        match child.wait() {
+            Ok(status) if !status.success() => eprintln!("Hook {} failed: {}", command, status),
+            Ok(_) => {},
+            Err(e) => eprintln!("Hook {} failed: {}", command, e),
+        }
+    });
+}
@@ -0,0 +1,151 @@
+use serde::{Serialize, Deserialize};
+
+/// Widest dashboard grid, in cells.
+const MAX_COLUMNS: u32 = 12;
+
+/// How the watches are shown: one at a time in tabs, or tiled side by side.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    #[default]
+    Tabs,
+    Grid,
+}
+
+impl LayoutMode {
+    pub fn parse(text: &str) -> Result<LayoutMode, String> {
+        match text.trim().to_lowercase().as_str() {
+            "tabs" => Ok(LayoutMode::Tabs),
+            "grid" => Ok(LayoutMode::Grid),
+            other => Err(format!("Unknown layout {}, use tabs or grid", other)),
+        }
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// Where a watch is shown in the grid, in cells from the top left corner.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub watch: String,  // Id of the watch
+    pub x: u32,
+    pub y: u32,
+    #[serde(default = "one")]
+    pub w: u32,
+    #[serde(default = "one")]
+    pub h: u32,
+}
+
+impl Tile {
+    /// Whether two tiles share a cell, widened so that no tile overflows.
+    fn overlaps(&self, other: &Tile) -> bool {
+        let (x, y, w, h) = (u64::from(self.x), u64::from(self.y), u64::from(self.w), u64::from(self.h));
+        let (ox, oy, ow, oh) = (u64::from(other.x), u64::from(other.y), u64::from(other.w), u64::from(other.h));
+        x < ox + ow && ox < x + w && y < oy + oh && oy < y + h
+    }
+}
+
+/// The dashboard: a grid of cells the watches are tiled on. Watches without
+/// a tile are placed by the UI in the first free cells.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Layout {
+    pub mode: LayoutMode,
+    pub columns: u32,
+    pub tiles: Vec<Tile>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { mode: LayoutMode::Tabs, columns: 2, tiles: Vec::new() }
+    }
+}
+
+impl Layout {
+    /// Checks that every tile fits in the columns of the grid, without
+    /// covering another one.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.columns == 0 || self.columns > MAX_COLUMNS {
+            return Err(format!("A dashboard has 1 to {} columns, not {}", MAX_COLUMNS, self.columns));
+        }
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if tile.w == 0 || tile.h == 0 {
+                return Err(format!("Tile of watch {} is empty", tile.watch));
+            }
+            if tile.x.checked_add(tile.w).is_none_or(|right| right > self.columns) {
+                return Err(format!("Tile of watch {} does not fit in {} columns", tile.watch, self.columns));
+            }
+            if tile.y.checked_add(tile.h).is_none() {
+                return Err(format!("Tile of watch {} goes past the last row", tile.watch));
+            }
+            if let Some(other) = self.tiles[..i].iter().find(|other| other.watch == tile.watch) {
+                return Err(format!("Watch {} has more than one tile", other.watch));
+            }
+            if let Some(other) = self.tiles[..i].iter().find(|other| other.overlaps(tile)) {
+                return Err(format!("Tiles of watches {} and {} overlap", other.watch, tile.watch));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(watch: &str, x: u32, y: u32, w: u32, h: u32) -> Tile {
+        Tile { watch: watch.to_string(), x, y, w, h }
+    }
+
+    fn grid(columns: u32, tiles: Vec<Tile>) -> Layout {
+        Layout { mode: LayoutMode::Grid, columns, tiles }
+    }
+
+    #[test]
+    fn tiles_side_by_side() {
+        let layout = grid(3, vec![tile("a", 0, 0, 2, 1), tile("b", 2, 0, 1, 2), tile("c", 0, 1, 2, 5)]);
+        assert_eq!(layout.validate(), Ok(()));
+        assert_eq!(Layout::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn tile_past_the_last_column() {
+        assert_eq!(grid(2, vec![tile("a", 1, 0, 2, 1)]).validate(),
+            Err("Tile of watch a does not fit in 2 columns".to_string()));
+        assert!(grid(2, vec![tile("a", 2, 0, 1, 1)]).validate().is_err());
+    }
+
+    #[test]
+    fn overlapping_tiles() {
+        assert_eq!(grid(4, vec![tile("a", 0, 0, 2, 2), tile("b", 1, 1, 2, 2)]).validate(),
+            Err("Tiles of watches a and b overlap".to_string()));
+        // Touching is fine
+        assert_eq!(grid(4, vec![tile("a", 0, 0, 2, 2), tile("b", 2, 0, 2, 2)]).validate(), Ok(()));
+    }
+
+    #[test]
+    fn watch_tiled_twice() {
+        assert_eq!(grid(4, vec![tile("a", 0, 0, 1, 1), tile("a", 2, 0, 1, 1)]).validate(),
+            Err("Watch a has more than one tile".to_string()));
+    }
+
+    #[test]
+    fn tiles_overflowing() {
+        assert_eq!(grid(2, vec![tile("a", u32::MAX, 0, 2, 1)]).validate(),
+            Err("Tile of watch a does not fit in 2 columns".to_string()));
+        assert_eq!(grid(2, vec![tile("a", 0, u32::MAX, 1, 1)]).validate(),
+            Err("Tile of watch a goes past the last row".to_string()));
+        // The last rows there are, far from the first tile
+        let far = grid(2, vec![tile("a", 0, 0, 1, 1), tile("b", 0, u32::MAX - 1, 1, 1)]);
+        assert_eq!(far.validate(), Ok(()));
+    }
+
+    #[test]
+    fn empty_tiles_and_bad_grids() {
+        assert!(grid(2, vec![tile("a", 0, 0, 0, 1)]).validate().is_err());
+        assert!(grid(0, Vec::new()).validate().is_err());
+        assert!(grid(MAX_COLUMNS + 1, Vec::new()).validate().is_err());
+    }
+}
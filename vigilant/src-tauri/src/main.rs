@@ -5,8 +5,10 @@ use futures_util::{SinkExt, StreamExt};
 use serde_json::Value;
 use serde_json::json;
 use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::fs;
 use serde::{Serialize, Deserialize};
-use std::thread;
 use std::process;
 use tokio::time::Duration;
 use tauri::Env;
@@ -26,28 +28,433 @@ struct SetTitle {
 #[derive(Serialize)]
 struct SetHeaders {
     command: String,
-    headers: Vec<String>,
+    headers: Vec<String>,  // Column labels to show
+    names: Vec<String>,    // Column names in the command output
+    available: Vec<String>,  // Every column of the table, including rates
+    kinds: Vec<value::CellKind>,
+    sort: Option<cmd::SortSpec>,
+    filter: Option<String>,
+    rates: Vec<String>,    // Columns followed by a COLUMN/s rate column
+    aggregates: Vec<String>,  // Footer aggregates
+    group_by: Option<String>,
+    alerts: Vec<String>,   // Alert rules being checked
 }
 
 #[derive(Serialize, Debug)]
 struct SetDataRow {
     command: String,
     index: i32,
+    key: String,           // Identifies the row from one run to the next
     values: Vec<String>,
+    numbers: Vec<Option<f64>>,  // Normalized value of each cell, None for text
+}
+
+#[derive(Serialize, Debug)]
+struct SetCellHistory {
+    command: String,
+    limit: usize,  // Points kept for each cell
+    rows: history::PointsByRow,  // (timestamp, value) points by row key and column
+}
+
+#[derive(Serialize, Debug)]
+struct AppendCellHistory {
+    command: String,
+    limit: usize,
+    time: f64,     // When the run was taken, seconds since the epoch
+    rows: history::ValuesByRow,  // New value by row key and column, rows left out are gone
+}
+
+#[derive(Serialize, Debug)]
+struct SetFooter {
+    command: String,
+    cells: Vec<aggregate::FooterCell>,
+}
+
+#[derive(Serialize, Debug)]
+struct SetGroups {
+    command: String,
+    column: String,
+    groups: Vec<cmd::Group>,
+}
+
+#[derive(Serialize, Debug)]
+struct HistorySeries {
+    key: String,
+    points: Vec<(f64, f64)>,  // (timestamp, value)
+}
+
+#[derive(Serialize, Debug)]
+struct HistoryResult {
+    command: String,
+    column: String,
+    series: Vec<HistorySeries>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct AlertFired {
+    command: String,
+    rule: String,
+    headers: Vec<String>,  // Every column of the table, as in the rows
+    rows: Vec<alert::AlertRow>,  // Rows which made the alert fire
+}
+
+#[derive(Serialize, Debug)]
+struct AlertResolved {
+    command: String,
+    rule: String,
+}
+
+#[derive(Serialize, Debug)]
+struct SetTimeline {
+    command: String,
+    first: u64,  // Oldest run which can be looked at
+    last: u64,   // Latest run
+}
+
+#[derive(Serialize, Debug)]
+struct SetSnapshot {
+    command: String,
+    #[serde(flatten)]
+    snapshot: cmd::Snapshot,
+}
+
+#[derive(Serialize, Debug)]
+struct SetRunDiff {
+    command: String,
+    #[serde(flatten)]
+    diff: cmd::RunDiff,
+}
+
+#[derive(Serialize, Debug)]
+struct SetReplayState {
+    command: String,
+    #[serde(flatten)]
+    state: session::ReplayState,
+}
+
+#[derive(Serialize, Debug)]
+struct ExportResult {
+    command: String,
+    filename: String,
+    content: String,
+}
+
+#[derive(Serialize, Debug)]
+struct SetText {
+    command: String,
+    lines: Vec<String>,
+    changed: Vec<usize>,
+}
+
+#[derive(Serialize, Debug)]
+struct SetRowCount {
+    command: String,
+    count: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct SetWatches {
+    command: String,
+    watches: Vec<WatchDef>,
+}
+
+#[derive(Serialize, Debug)]
+struct SetLayout {
+    command: String,
+    layout: layout::Layout,
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorMessage {
+    command: String,
+    message: String,
+}
+
+/// The watch a message from the UI is about, the first one when not given.
+#[derive(Deserialize, Debug, Default)]
+struct Target {
+    watch: Option<String>,
+}
+
+/// Messages sent by the UI about the window rather than one watch.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "command")]
+enum WindowMessage {
+    SetLayout { layout: layout::Layout },
+    AddWatch { definition: WatchDef },
+    UpdateWatch { definition: WatchDef },
+    RemoveWatch { id: String },
+}
+
+/// Messages sent by the UI to the server.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "command")]
+enum ClientMessage {
+    SetSort { column: Option<String>, direction: cmd::SortDirection },
+    SetFilter { filter: Option<String> },
+    SetColumns { columns: Option<Vec<cmd::ColumnSpec>> },
+    SetRate { column: String, enabled: bool },
+    SetAggregates { aggregates: Vec<String> },
+    SetGroupBy { column: Option<String> },
+    QueryHistory { keys: Vec<String>, column: String, from: Option<f64>, to: Option<f64> },
+    GetSnapshot { run_id: u64 },
+    DiffRuns { from: u64, to: u64 },
+    SetReplay { paused: Option<bool>, speed: Option<f64> },
+    StepReplay,
+    Export { format: export::ExportFormat },
+    ExportHistory { columns: Vec<String>, from: Option<f64>, to: Option<f64>, format: export::ExportFormat },
+}
+
+/// Command run when none is given nor configured.
+const DEFAULT_COMMAND: &str = "/usr/bin/lsfd";
+
+/// A command watched in a tab of its own, with how its output is parsed
+/// and shown.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+struct WatchDef {
+    id: String,               // Names the watch in the protocol
+    cmdline: String,
+    interval: Option<u64>,    // Seconds between runs, the global interval when None
+    raw: bool,                // Show the output as text instead of a table
+    filter: Option<String>,
+    columns: Option<Vec<cmd::ColumnSpec>>,
+    key: Option<String>,
+    rates: Vec<String>,
+    aggregates: Vec<String>,
+    group_by: Option<String>,
+}
+
+impl WatchDef {
+    fn new(cmdline: &str) -> WatchDef {
+        WatchDef {
+            cmdline: cmdline.split_whitespace().collect::<Vec<_>>().join(" "),
+            ..Default::default()
+        }
+    }
+
+    /// Checks a definition sent by the UI, tidying up its command line.
+    fn validate(&mut self) -> Result<(), String> {
+        self.cmdline = self.cmdline.split_whitespace().collect::<Vec<_>>().join(" ");
+        if self.cmdline.is_empty() {
+            return Err("A watch needs a command to run".to_string());
+        }
+        if self.interval == Some(0) {
+            return Err(format!("Watch {} needs at least 1 second between runs", self.cmdline));
+        }
+        if let Some(ref text) = self.filter {
+            filter::Filter::parse(text)?;
+        }
+        // Column names are checked against the output once the watch runs
+        if let Some(ref columns) = self.columns {
+            if columns.is_empty() {
+                return Err("No columns selected".to_string());
+            }
+            if columns.iter().any(|c| c.name.trim().is_empty()) {
+                return Err("A selected column has no name".to_string());
+            }
+        }
+        if self.rates.iter().any(|c| c.trim().is_empty()) {
+            return Err("A rate column has no name".to_string());
+        }
+        for text in &self.aggregates {
+            aggregate::Aggregate::check(text)?;
+        }
+        Ok(())
+    }
+
+    /// Numbers the watches without an id, or with one already taken.
+    fn assign_ids(defs: &mut [WatchDef]) {
+        let mut taken: Vec<String> = Vec::new();
+        for def in defs.iter_mut() {
+            if def.id.trim().is_empty() || taken.contains(&def.id) {
+                let mut n = taken.len() + 1;
+                while taken.contains(&n.to_string()) {
+                    n += 1;
+                }
+                def.id = n.to_string();
+            }
+            taken.push(def.id.clone());
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 struct GConf {
+    #[serde(skip)]
     cmdline: Vec<String>,  // Command line arguments
+    #[serde(skip)]
+    raw: bool,             // Show output as text instead of a table, given with --raw
+    interval: u64,         // Seconds between updates
+    #[serde(skip)]
+    cli_interval: Option<u64>,  // Given with -n, for this session only
+    history: usize,        // Values kept for each numeric cell
+    #[serde(skip)]
+    cli_history: Option<usize>,  // Given with --history, for this session only
+    sorts: HashMap<String, cmd::SortSpec>,  // Sort column for each command line
+    watches: Vec<WatchDef>,      // Commands shown in tabs when none is given
+    #[serde(skip)]
+    active: Vec<WatchDef>,       // Commands being watched
+    #[serde(skip)]
+    save_watches: bool,          // Whether changes to them are saved, not when given on the command line
+    store: bool,                 // Keep every run on disk
+    store_dir: Option<String>,   // Where runs are kept, the XDG data directory when None
+    keep_runs: Option<usize>,    // Retention of the stored runs
+    keep_age: Option<String>,    // e.g. 30d
+    keep_size: Option<String>,   // e.g. 256M
+    #[serde(skip)]
+    cli_no_store: bool,          // Given with --no-store, the store settings below with --store-dir etc.
+    #[serde(skip)]
+    cli_store_dir: Option<String>,
+    #[serde(skip)]
+    cli_keep_runs: Option<usize>,
+    #[serde(skip)]
+    cli_keep_age: Option<String>,
+    #[serde(skip)]
+    cli_keep_size: Option<String>,
+    alerts: Vec<String>,         // Alert rules checked for every command, e.g. %CPU > 90 for 3 runs
+    #[serde(skip)]
+    cli_alerts: Vec<String>,     // Alert rules given on the command line, checked as well
+    #[serde(skip)]
+    alert_on: Vec<String>,       // Patterns raising an alert when a line matches
+    #[serde(skip)]
+    alert_off: Vec<String>,      // Patterns raising an alert when no line matches
+    #[serde(skip)]
+    exit_on_match: bool,         // Exit once an alert fires
+    notify: bool,                // Desktop notifications while in the background
+    #[serde(skip)]
+    cli_notify: bool,            // Given with --notify, for this session only
+    notify_gap: u64,             // Seconds between two notifications at least
+    #[serde(skip)]
+    chgexit: bool,               // Exit when the output changes
+    #[serde(skip)]
+    timeout: Option<u64>,        // Seconds a run may take before the command is killed
+    #[serde(skip)]
+    on_change: Option<String>,   // Shell command run when the output changes
+    #[serde(skip)]
+    on_alert: Option<String>,    // Shell command run when an alert fires or resolves
+    webhooks: Vec<webhook::WebhookConfig>,  // Endpoints events are posted to
+    #[serde(skip)]
+    cli_webhooks: Vec<webhook::WebhookConfig>,  // Given on the command line, posted to as well
+    #[serde(skip)]
+    filter: Option<String>,  // Only rows matching this are shown
+    #[serde(skip)]
+    columns: Option<Vec<cmd::ColumnSpec>>,  // Columns to show, all when None
+    #[serde(skip)]
+    key: Option<String>,     // Column identifying a row across runs
+    #[serde(skip)]
+    rates: Vec<String>,      // Counter columns to show a per-second rate for
+    #[serde(skip)]
+    aggregates: Vec<String>, // Shown in the footer, e.g. sum(%MEM)
+    #[serde(skip)]
+    group_by: Option<String>,  // Column rows are grouped by
+    #[serde(skip)]
+    speed: f64,              // Replay speed, 1.0 as recorded
+    layout: layout::Layout,  // Whether watches are shown in tabs or tiled, and where
+    #[serde(skip)]
+    cli_layout_mode: Option<layout::LayoutMode>,  // Given on the command line, until changed from the UI
 }
 
 mod cmd;
+mod value;
+mod filter;
+mod history;
+mod aggregate;
+mod store;
+mod session;
+mod export;
+mod alert;
+mod notify;
+mod hook;
+mod webhook;
+mod layout;
+
+impl Default for GConf {
+    fn default() -> Self {
+        GConf::new()
+    }
+}
 
 impl GConf {
     fn new() -> Self {
         GConf {
             cmdline: Vec::new(),
+            raw: false,
+            interval: 10,
+            cli_interval: None,
+            history: 60,
+            cli_history: None,
+            sorts: HashMap::new(),
+            watches: Vec::new(),
+            active: Vec::new(),
+            save_watches: false,
+            store: true,
+            store_dir: None,
+            keep_runs: Some(10000),
+            keep_age: Some("30d".to_string()),
+            keep_size: Some("256M".to_string()),
+            cli_no_store: false,
+            cli_store_dir: None,
+            cli_keep_runs: None,
+            cli_keep_age: None,
+            cli_keep_size: None,
+            alerts: Vec::new(),
+            cli_alerts: Vec::new(),
+            alert_on: Vec::new(),
+            alert_off: Vec::new(),
+            exit_on_match: false,
+            notify: false,
+            cli_notify: false,
+            notify_gap: 60,
+            chgexit: false,
+            timeout: None,
+            on_change: None,
+            on_alert: None,
+            webhooks: Vec::new(),
+            cli_webhooks: Vec::new(),
+            filter: None,
+            columns: None,
+            key: None,
+            rates: Vec::new(),
+            aggregates: Vec::new(),
+            group_by: None,
+            speed: 1.0,
+            layout: layout::Layout::default(),
+            cli_layout_mode: None,
+        }
+    }
+
+    /// Location of the saved configuration, following the XDG convention.
+    fn config_path() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("vigilant").join("config.json"))
+    }
+
+    /// Loads the saved configuration, or the defaults when there is none.
+    fn load() -> Self {
+        let conf = Self::config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| match serde_json::from_str::<GConf>(&text) {
+                Ok(conf) => Some(conf),
+                Err(e) => {
+                    eprintln!("Ignoring bad configuration file: {}", e);
+                    None
+                }
+            });
+        conf.unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::config_path().ok_or("No home directory for the configuration")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
         }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
     }
 
     fn set_cmdline(&mut self, cmdline: &str) {
@@ -61,13 +468,251 @@ impl GConf {
     fn get_cmdline(&self) -> String {
         self.cmdline.join(" ")
     }
+
+    fn get_sort(&self, cmdline: &str) -> Option<cmd::SortSpec> {
+        self.sorts.get(cmdline).cloned()
+    }
+
+    fn set_sort(&mut self, cmdline: &str, sort: Option<cmd::SortSpec>) {
+        match sort {
+            Some(spec) => self.sorts.insert(cmdline.to_string(), spec),
+            None => self.sorts.remove(cmdline),
+        };
+    }
+
+    /// Sets the commands watched: those given on the command line, else
+    /// those of the configuration file, else the default one. Table
+    /// settings given on the command line apply to every one of them.
+    fn resolve_watches(&mut self, commands: &[String]) {
+        let mut active: Vec<WatchDef> = if !commands.is_empty() {
+            commands.iter().map(|c| WatchDef::new(c)).collect()
+        } else if !self.watches.is_empty() {
+            self.watches.clone()
+        } else {
+            vec![WatchDef::new(DEFAULT_COMMAND)]
+        };
+        for def in active.iter_mut() {
+            def.raw = def.raw || self.raw;
+            if self.filter.is_some() {
+                def.filter = self.filter.clone();
+            }
+            if self.columns.is_some() {
+                def.columns = self.columns.clone();
+            }
+            if self.key.is_some() {
+                def.key = self.key.clone();
+            }
+            if !self.rates.is_empty() {
+                def.rates = self.rates.clone();
+            }
+            if !self.aggregates.is_empty() {
+                def.aggregates = self.aggregates.clone();
+            }
+            if self.group_by.is_some() {
+                def.group_by = self.group_by.clone();
+            }
+        }
+        WatchDef::assign_ids(&mut active);
+        self.active = active;
+        self.save_watches = commands.is_empty();
+    }
+
+    /// Adds a watch, numbered unless given an id of its own. Returns the id.
+    fn add_watch(&mut self, mut def: WatchDef) -> Result<String, String> {
+        def.validate()?;
+        if self.active.iter().any(|other| other.id == def.id) {
+            return Err(format!("There is already a watch {}", def.id));
+        }
+        self.active.push(def);
+        WatchDef::assign_ids(&mut self.active);
+        self.watches_changed();
+        Ok(self.active[self.active.len() - 1].id.clone())
+    }
+
+    /// Replaces the definition of the watch with the same id.
+    fn update_watch(&mut self, mut def: WatchDef) -> Result<String, String> {
+        def.validate()?;
+        let id = def.id.clone();
+        let slot = self.watch_mut(&id).ok_or_else(|| format!("No watch {}", id))?;
+        *slot = def;
+        self.watches_changed();
+        Ok(id)
+    }
+
+    /// Removes a watch and its tile, the last one is kept.
+    fn remove_watch(&mut self, id: &str) -> Result<String, String> {
+        if !self.active.iter().any(|def| def.id == id) {
+            return Err(format!("No watch {}", id));
+        }
+        if self.active.len() == 1 {
+            return Err("The last watch cannot be removed".to_string());
+        }
+        self.active.retain(|def| def.id != id);
+        self.layout.tiles.retain(|tile| tile.watch != id);
+        self.watches_changed();
+        Ok(id.to_string())
+    }
+
+    /// Saves the watches after a change made from the UI, when they came
+    /// from the configuration file.
+    fn watches_changed(&mut self) {
+        if !self.save_watches {
+            return;
+        }
+        self.watches = self.active.iter().map(|def| self.saved_watch(def)).collect();
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save configuration: {}", e);
+        }
+    }
+
+    /// A watch as it is saved: the table settings given on the command line
+    /// are for this session only, those still in use are put back to the
+    /// ones saved before.
+    fn saved_watch(&self, def: &WatchDef) -> WatchDef {
+        let before = self.watches.iter().find(|saved| saved.id == def.id);
+        let mut def = def.clone();
+        if self.raw && def.raw {
+            def.raw = before.is_some_and(|saved| saved.raw);
+        }
+        if self.filter.is_some() && def.filter == self.filter {
+            def.filter = before.and_then(|saved| saved.filter.clone());
+        }
+        if self.columns.is_some() && def.columns == self.columns {
+            def.columns = before.and_then(|saved| saved.columns.clone());
+        }
+        if self.key.is_some() && def.key == self.key {
+            def.key = before.and_then(|saved| saved.key.clone());
+        }
+        if !self.rates.is_empty() && def.rates == self.rates {
+            def.rates = before.map(|saved| saved.rates.clone()).unwrap_or_default();
+        }
+        if !self.aggregates.is_empty() && def.aggregates == self.aggregates {
+            def.aggregates = before.map(|saved| saved.aggregates.clone()).unwrap_or_default();
+        }
+        if self.group_by.is_some() && def.group_by == self.group_by {
+            def.group_by = before.and_then(|saved| saved.group_by.clone());
+        }
+        def
+    }
+
+    /// The definition of a watch being run, changed as its table is set up
+    /// from the UI.
+    fn watch_mut(&mut self, id: &str) -> Option<&mut WatchDef> {
+        self.active.iter_mut().find(|def| def.id == id)
+    }
+
+    /// The table settings of a watch written to a recording.
+    fn session_config(&self, def: &WatchDef) -> session::SessionConfig {
+        session::SessionConfig {
+            raw: def.raw,
+            filter: def.filter.clone(),
+            columns: def.columns.clone(),
+            key: def.key.clone(),
+            rates: def.rates.clone(),
+            aggregates: def.aggregates.clone(),
+            group_by: def.group_by.clone(),
+            sort: self.get_sort(&def.cmdline),
+        }
+    }
+
+    /// Takes the settings of a recording being replayed, except those given
+    /// on the command line.
+    fn apply_session_config(&mut self, cmdline: &str, config: &session::SessionConfig) {
+        self.raw = self.raw || config.raw;
+        if self.filter.is_none() {
+            self.filter = config.filter.clone();
+        }
+        if self.columns.is_none() {
+            self.columns = config.columns.clone();
+        }
+        if self.key.is_none() {
+            self.key = config.key.clone();
+        }
+        if self.rates.is_empty() {
+            self.rates = config.rates.clone();
+        }
+        if self.aggregates.is_empty() {
+            self.aggregates = config.aggregates.clone();
+        }
+        if self.group_by.is_none() {
+            self.group_by = config.group_by.clone();
+        }
+        if self.get_sort(cmdline).is_none() {
+            self.set_sort(cmdline, config.sort.clone());
+        }
+    }
+
+    /// Alert rules from the configuration file followed by those given on
+    /// the command line.
+    fn alert_rules(&self) -> Vec<String> {
+        self.alerts.iter().chain(self.cli_alerts.iter()).cloned().collect()
+    }
+
+    /// Triggers for the --alert-on and --alert-off patterns.
+    fn line_triggers(&self) -> Result<Vec<alert::LineTrigger>, String> {
+        let on = self.alert_on.iter().map(|p| alert::LineTrigger::parse(p, true));
+        let off = self.alert_off.iter().map(|p| alert::LineTrigger::parse(p, false));
+        on.chain(off).collect()
+    }
+
+    /// Webhooks from the configuration file followed by those given on the
+    /// command line.
+    fn webhook_configs(&self) -> Vec<webhook::WebhookConfig> {
+        self.webhooks.iter().chain(self.cli_webhooks.iter()).cloned().collect()
+    }
+
+    /// Seconds between runs of watches without an interval of their own,
+    /// the one given on the command line if any.
+    fn interval(&self) -> u64 {
+        self.cli_interval.unwrap_or(self.interval).max(1)
+    }
+
+    /// Values kept for each numeric cell, as given on the command line if
+    /// it was.
+    fn history(&self) -> usize {
+        self.cli_history.unwrap_or(self.history)
+    }
+
+    /// Whether desktop notifications are shown, for good or with --notify.
+    fn notify(&self) -> bool {
+        self.notify || self.cli_notify
+    }
+
+    /// The dashboard layout, in the mode given on the command line if any.
+    fn layout(&self) -> layout::Layout {
+        let mut layout = self.layout.clone();
+        if let Some(mode) = self.cli_layout_mode {
+            layout.mode = mode;
+        }
+        layout
+    }
+
+    /// Opens the store keeping the runs of a command line, None when runs
+    /// are not stored. Settings given on the command line take precedence.
+    fn open_store(&self, cmdline: &str) -> Result<Option<store::RunStore>, String> {
+        if !self.store || self.cli_no_store {
+            return Ok(None);
+        }
+        let base = match self.cli_store_dir.as_ref().or(self.store_dir.as_ref()) {
+            Some(dir) => PathBuf::from(dir),
+            None => store::default_dir().ok_or("No home directory for the run store")?,
+        };
+        let retention = store::Retention::parse(
+            self.cli_keep_runs.or(self.keep_runs),
+            self.cli_keep_age.as_deref().or(self.keep_age.as_deref()),
+            self.cli_keep_size.as_deref().or(self.keep_size.as_deref()),
+        )?;
+        store::RunStore::open(store::command_dir(&base, cmdline), retention)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
 }
 
 /*
  * Rust clap documentation
  * https://www.shuttle.dev/blog/2023/12/08/clap-rust
  */
-use clap::{Parser, Arg};
+use clap::{Parser, Subcommand, Arg};
 
 /// Watch for command output with various options.
 #[derive(Parser)]
@@ -89,7 +734,7 @@ struct Args {
     #[arg(short = 'e', long = "errexit")]
     errexit: bool,
 
-    /// Exit when output from command changes
+    /// Exit when output from command changes, notifying first with --notify
     #[arg(short = 'g', long = "chgexit")]
     chgexit: bool,
 
@@ -117,170 +762,1768 @@ struct Args {
     #[arg(short = 'x', long = "exec")]
     exec: bool,
 
-    /// The command to run
-    #[arg(default_value = "/usr/bin/lsfd")]
-    command: String,
-}
+    /// Show the output as plain text, exactly like watch(1), instead of a table
+    #[arg(short = 'r', long = "raw")]
+    raw: bool,
 
-#[tokio::main]
-async fn main() {
-    let args = Args::parse();
-    let gconf = Arc::new(Mutex::new(GConf::new()));
+    /// Only show rows matching the expression, e.g. 'STATE == "ESTAB" && PORT > 1024'
+    #[arg(short = 'f', long = "filter", value_name = "expr")]
+    filter: Option<String>,
 
-    println!("arguments to run {}", args.command);
-    gconf.lock().unwrap().set_cmdline(&args.command.to_string());
-    tauri::Builder::default()
-        .setup(move |_app| {
-            let gconf_clone = Arc::clone(&gconf);
-            tokio::spawn(async move {
-                start_websocket_server(gconf_clone).await;
-            });
-            Ok(())
-        })
-        .run(tauri::generate_context!())
-        .expect("Error while running tauri application");
-}
+    /// Only show these columns, in this order, optionally renamed: PID,COMMAND=Command,%CPU
+    #[arg(long = "columns", value_name = "list")]
+    columns: Option<String>,
 
-use tokio_tungstenite::WebSocketStream;
-use tokio_tungstenite::tungstenite::error::Error;
-use futures_util::stream::SplitSink;
+    /// Column identifying a row from one run to the next, the first one by default
+    #[arg(long = "key", value_name = "column")]
+    key: Option<String>,
 
-pub async fn send_json_message<T>(
-    writer: &mut SplitSink<WebSocketStream<tokio::net::TcpStream>, Message>,
-    rdata: T,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-    T: Serialize,
-{
-    // Serialize the data to JSON
-    let json_message = serde_json::to_string(&rdata).map_err(|e| {
-        eprintln!("Serialization error: {}", e);
-        e
-    })?;
+    /// Add a COLUMN/s column with the per-second rate of a counter column (repeatable)
+    #[arg(long = "rate", value_name = "column")]
+    rate: Vec<String>,
 
-    // Send the message
-    writer.send(Message::Text(json_message.clone())).await.map_err(|e| {
-        eprintln!("Error sending message: {}", e);
-        e
-    })?;
+    /// Number of values kept for each numeric cell, shown as sparklines
+    #[arg(long = "history", value_name = "count")]
+    history: Option<usize>,
 
-    println!("Sent message: {}", json_message);
-    Ok(())
-}
+    /// Show an aggregate in the footer: sum, avg, min or max of a column,
+    /// count() or count(expr), e.g. 'sum(%MEM)' (repeatable)
+    #[arg(long = "footer", value_name = "func(column)")]
+    footer: Vec<String>,
 
-async fn start_websocket_server(gconf: Arc<Mutex<GConf>>) {
-    let addr = "127.0.0.1:8080".parse::<SocketAddr>().unwrap();
-    let listener = TcpListener::bind(&addr).await.unwrap();
-    println!("WebSocket server listening on {}", addr);
+    /// Group rows by the value of a column, with counts and footer aggregates per group
+    #[arg(long = "group-by", value_name = "column")]
+    group_by: Option<String>,
 
-    while let Ok((stream, _)) = listener.accept().await {
-        let ws_stream = accept_async(stream).await.unwrap();
-        println!("New WebSocket connection");
+    /// Raise an alert when rows match an expression, for a number of runs
+    /// in a row when followed by 'for N runs', e.g. '%CPU > 90 for 3 runs' (repeatable)
+    #[arg(long = "alert", value_name = "rule")]
+    alert: Vec<String>,
 
-        let (mut writer, mut reader) = ws_stream.split();
+    /// Raise an alert when a line of the output matches a regular expression,
+    /// e.g. 'ERROR' (repeatable)
+    #[arg(long = "alert-on", value_name = "regex")]
+    alert_on: Vec<String>,
 
-        // Sending the welcome message
-        let welcome_message = WelcomeMessage {
-            message: "Welcome to the WebSocket server!".to_string(),
-            timestamp: chrono::Utc::now().to_rfc3339(),
-        };
+    /// Raise an alert when no line of the output matches a regular expression,
+    /// e.g. 'Ready' (repeatable)
+    #[arg(long = "alert-off", value_name = "regex")]
+    alert_off: Vec<String>,
 
-        let json_message = serde_json::to_string(&welcome_message).unwrap();
-        //writer.send(Message::Text(json_message)).await.unwrap();
-        if let Err(e) = writer.send(Message::Text(json_message)).await {
-            // Handle the error (e.g., logging)
-            eprintln!("Error sending message: {}", e);
+    /// Exit once an alert fires, printing what fired it
+    #[arg(long = "exit-on-match")]
+    exit_on_match: bool,
 
-            process::exit(1);
-        }
+    /// Show desktop notifications for alerts, failures, timeouts and, with
+    /// --chgexit, output changes while the window is in the background
+    #[arg(long = "notify")]
+    notify: bool,
 
-        // Sending the title message
-        let mut title_message = SetTitle {
-            command: "SetTitle".to_string(),
-            center: "Center".to_string(),
-        };
+    /// Kill the command when a run takes longer than this
+    #[arg(long = "timeout", value_name = "secs")]
+    timeout: Option<u64>,
 
-        let cmdline = gconf.lock().unwrap().get_cmdline();
-        title_message.center = cmdline.clone();
-        println!("Cmdline: {}", cmdline);
-        println!("title msg : {:?}", title_message);
+    /// Run a shell command when the output changes, given the changes as
+    /// JSON on its standard input and VIGILANT_* environment variables
+    #[arg(long = "on-change", value_name = "cmd")]
+    on_change: Option<String>,
 
-        if let Err(e) = send_json_message(&mut writer, title_message).await {
-            eprintln!("Failed to send title message: {}", e);
-        }
+    /// Run a shell command when an alert fires or resolves, given the rows
+    /// as JSON on its standard input and VIGILANT_* environment variables
+    #[arg(long = "on-alert", value_name = "cmd")]
+    on_alert: Option<String>,
 
-        let mut colines = cmd::CmdOutput::new(&cmdline.clone());
-
-        // Sending headers message
-        match colines {
-            Ok(ref mut colines_ref) => match colines_ref.get_headers() {
-                Ok(hdrs) => {
-                    println!("Headers: {:?}", hdrs);
-                    // Create the SetHeaders message using hdrs
-                    let hdr_message = SetHeaders {
-                        command: "SetHeaders".to_string(),
-                        headers: hdrs,
-                    };
-                    if let Err(e) = send_json_message(&mut writer, hdr_message).await {
-                        eprintln!("Failed to send headers: {}", e);
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Error getting headers: {}", e);
-                },
-            },
-            Err(ref e) => eprintln!("Error: {}", e), // Handling the error case from colines
-        }
+    /// POST alert and change events as JSON to a URL, retrying on failure (repeatable)
+    #[arg(long = "webhook", value_name = "url")]
+    webhook: Vec<String>,
 
-        // Sending data rows
-        let mut idx = 0;
-        while let Some(lines) = colines.as_mut().expect("Reee").next() {
-            let r = SetDataRow {
-                command: "SetDataRow".to_string(),
-                index: idx,
-                values: lines,
-            };
-            if let Err(e) = send_json_message(&mut writer, r).await {
-                eprintln!("Failed to send data row: {}", e);
-            }
-            idx += 1;
-        }
+    /// Sign the --webhook deliveries with this secret, read from
+    /// VIGILANT_WEBHOOK_SECRET when not given
+    #[arg(long = "webhook-secret", value_name = "secret")]
+    webhook_secret: Option<String>,
 
-        loop {
-            println!("10 seconds have passed");
-            thread::sleep(Duration::from_secs(10));
-            let mut oplines: Vec<String>;
-            match colines.as_mut().expect("cmd bad").clone().execute(&cmdline.clone()) {
-                Ok(oplines) => {
-                    //println!("{} Outputs {:?}", cmdline.clone(), oplines);
-                    println!("No of output lines {}", oplines.len());
-                    let changed = colines.as_mut().expect("cmd bad 2").update_lines(oplines.clone()).expect("update bad");
-                    for idx in 0..changed.len() {
-                        let olines = match colines.as_ref() {
-                            Ok(colines_ref) => match colines_ref.clone().get_output_cols(changed[idx]) {
-                                Some(lines) => lines,
-                                None => {
-                                    eprintln!("Error getting output line for index {}", idx);
-                                    return;
-                                }
-                            },
-                            Err(e) => {
-                                eprintln!("Error: failed to access colines: {}", e);
-                                return; // Propagate the error or handle it appropriately
-                            }
-                        };
-                        let r = SetDataRow {
-                            command: "SetDataRow".to_string(),
-                            index: idx as i32,
-                            values: olines,
-                        };
-                        if let Err(e) = send_json_message(&mut writer, r).await {
-                            eprintln!("Failed to send data row: {}", e);
-                        }
-                    }
-                },
-                Err(e) => println!("Error executing command {:?}", e),
-            }
-        }
-    }
+    /// Do not keep the runs on disk, by default they are kept under
+    /// $XDG_DATA_HOME/vigilant/runs (see docs/run-store.md)
+    #[arg(long = "no-store")]
+    no_store: bool,
+
+    /// Directory the runs are kept in, one subdirectory per command line
+    #[arg(long = "store-dir", value_name = "dir")]
+    store_dir: Option<String>,
+
+    /// Number of stored runs to keep
+    #[arg(long = "keep-runs", value_name = "count")]
+    keep_runs: Option<usize>,
+
+    /// Age after which stored runs are removed, e.g. 12h or 30d
+    #[arg(long = "keep-age", value_name = "age")]
+    keep_age: Option<String>,
+
+    /// Disk space the stored runs may use, e.g. 512M
+    #[arg(long = "keep-size", value_name = "size")]
+    keep_size: Option<String>,
+
+    /// Play back a recorded session instead of running a command
+    #[arg(long = "replay", value_name = "file")]
+    replay: Option<PathBuf>,
+
+    /// Record every run to a file, which --replay plays back
+    #[arg(long = "record", value_name = "file")]
+    record: Option<PathBuf>,
+
+    /// Replay speed, 2 plays twice as fast as recorded
+    #[arg(long = "speed", value_name = "factor")]
+    speed: Option<f64>,
+
+    /// Show the watches in tabs, or tiled together as laid out in the configuration: tabs or grid
+    #[arg(long = "layout", value_name = "mode")]
+    layout: Option<String>,
+
+    /// Watch another command as well, in a tab of its own, quoted as one
+    /// argument and given before the command: --watch 'df -h' (repeatable)
+    #[arg(long = "watch", value_name = "cmd")]
+    watch: Vec<String>,
+
+    /// The command to run, in the first tab; the watches of the configuration
+    /// file, else lsfd, when neither it nor --watch is given. A command named
+    /// export is given after --
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "command")]
+    command: Vec<String>,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+/// What vigilant can do besides watching.
+#[derive(Subcommand)]
+enum Action {
+    /// Run a command once and export its output as a table
+    Export(ExportArgs),
+}
+
+impl Args {
+    /// The commands given on the command line, the positional one first.
+    fn commands(&self) -> Vec<String> {
+        let first = (!self.command.is_empty()).then(|| self.command.join(" "));
+        first.into_iter().chain(self.watch.iter().cloned()).collect()
+    }
+}
+
+/// Runs a command once and writes its table, as `vigilant export`.
+#[derive(clap::Args)]
+struct ExportArgs {
+    /// Output format: csv, jsonl, markdown or html
+    #[arg(long = "format", default_value = "csv")]
+    format: String,
+
+    /// Write to this file instead of the standard output
+    #[arg(short = 'o', long = "output", value_name = "file")]
+    output: Option<PathBuf>,
+
+    /// Only export rows matching an expression, a /regex/ or a substring
+    #[arg(short = 'f', long = "filter", value_name = "expr")]
+    filter: Option<String>,
+
+    /// Only export these columns, in this order, optionally renamed: PID,COMMAND=Command,%CPU
+    #[arg(long = "columns", value_name = "list")]
+    columns: Option<String>,
+
+    /// Column identifying a row, the first one by default
+    #[arg(long = "key", value_name = "column")]
+    key: Option<String>,
+
+    /// Sort by a column, COLUMN:desc for descending; the sort saved for the command otherwise
+    #[arg(long = "sort", value_name = "column[:desc]")]
+    sort: Option<String>,
+
+    /// Export the stored values of these numeric columns instead of the
+    /// table, one timestamp,row_key,column,value record each: %CPU,RSS/s
+    #[arg(long = "history", value_name = "columns")]
+    history: Option<String>,
+
+    /// Oldest history exported, a time such as 2026-10-19T14:02:00 or an age such as 2h
+    #[arg(long = "from", value_name = "time")]
+    from: Option<String>,
+
+    /// Newest history exported, a time or an age
+    #[arg(long = "to", value_name = "time")]
+    to: Option<String>,
+
+    /// Take the history from a recording instead of the stored runs
+    #[arg(long = "recording", value_name = "file")]
+    recording: Option<PathBuf>,
+
+    /// The command to run, lsfd when not given
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "command")]
+    command: Vec<String>,
+}
+
+impl ExportArgs {
+    /// The command to run, as one command line.
+    fn cmdline(&self) -> String {
+        match self.command.join(" ").split_whitespace().collect::<Vec<_>>().join(" ") {
+            cmdline if cmdline.is_empty() => DEFAULT_COMMAND.to_string(),
+            cmdline => cmdline,
+        }
+    }
+}
+
+/// Parses a time given on the command line, either absolute or as an age.
+fn parse_time(text: &str) -> Result<f64, String> {
+    match value::parse_cell(text) {
+        (value::CellKind::Timestamp, Some(secs)) | (value::CellKind::Integer, Some(secs)) => Ok(secs),
+        (value::CellKind::Duration, Some(age)) => Ok(chrono::Utc::now().timestamp_millis() as f64 / 1000.0 - age),
+        _ => Err(format!("Bad time {}, expected e.g. 2026-10-19T14:02:00 or 2h", text)),
+    }
+}
+
+/// Writes `vigilant export --history`, from the stored runs of the command
+/// or from a recording, without running the command.
+fn export_history(args: ExportArgs, format: export::ExportFormat, columns: &str) -> Result<String, String> {
+    let from = args.from.as_deref().map(parse_time).transpose()?;
+    let to = args.to.as_deref().map(parse_time).transpose()?;
+    let (cmdline, runs) = match args.recording {
+        Some(ref path) => {
+            let session = session::read(path)?;
+            let runs = session.runs.into_iter()
+                .filter(|run| from.is_none_or(|t| run.timestamp >= t))
+                .filter(|run| to.is_none_or(|t| run.timestamp <= t))
+                .collect();
+            (session.header.cmdline, runs)
+        },
+        None => {
+            let cmdline = args.cmdline();
+            let store = GConf::load().open_store(&cmdline)?
+                .ok_or("Runs are not stored, see the store setting of the configuration")?;
+            let runs = store.range(from, to).map_err(|e| e.to_string())?;
+            (cmdline, runs)
+        },
+    };
+    // Columns are looked up in the latest run, like the table shows them
+    let latest = runs.iter().rev().find(|run| run.status == Some(0))
+        .ok_or_else(|| format!("No runs of {} in that time", cmdline))?
+        .clone();
+    let mut colines = cmd::CmdOutput::from_run(&cmdline, latest);
+    colines.set_key(args.key)?;
+    let columns: Vec<String> = columns.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+    let points = colines.history_points(&runs, &columns)?;
+    export::render_history(format, &points)
+}
+
+/// Runs `vigilant export`, applying the same filter, columns and sort as the
+/// table would.
+fn run_export(args: ExportArgs) -> Result<(), String> {
+    let format = export::ExportFormat::parse(&args.format)?;
+    let output = args.output.clone();
+    if let Some(columns) = args.history.clone() {
+        let text = export_history(args, format, &columns)?;
+        return write_export(output, text);
+    }
+    let cmdline = args.cmdline();
+    let mut colines = cmd::CmdOutput::new(&cmdline)
+        .map_err(|e| format!("Cannot run {}: {}", cmdline, e))?;
+    colines.get_headers().map_err(|e| e.to_string())?;
+
+    let sort = match args.sort {
+        Some(ref text) => {
+            let (column, direction) = match text.rsplit_once(':') {
+                Some((column, "desc")) => (column, cmd::SortDirection::Desc),
+                Some((column, "asc")) => (column, cmd::SortDirection::Asc),
+                _ => (text.as_str(), cmd::SortDirection::Asc),
+            };
+            Some(cmd::SortSpec { column: column.to_string(), direction })
+        },
+        None => GConf::load().get_sort(&cmdline),
+    };
+    colines.set_key(args.key)?;
+    colines.set_sort(sort)?;
+    colines.set_filter(args.filter.as_deref())?;
+    colines.set_columns(args.columns.as_deref().map(cmd::ColumnSpec::parse_list))?;
+    colines.update_view();
+
+    let (_, labels, _) = colines.get_view_headers();
+    let text = export::render(format, &cmdline, &labels, &colines.get_view_rows());
+    write_export(output, text)
+}
+
+fn write_export(output: Option<PathBuf>, text: String) -> Result<(), String> {
+    match output {
+        Some(path) => fs::write(&path, text).map_err(|e| format!("Cannot write {}: {}", path.display(), e)),
+        None => {
+            print!("{}", text);
+            Ok(())
+        },
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = Args::parse();
+    // `vigilant export ...` runs once and exits, without any window
+    if let Some(Action::Export(export_args)) = args.action.take() {
+        if let Err(e) = run_export(export_args) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    let gconf = Arc::new(Mutex::new(GConf::load()));
+
+    println!("arguments to run {:?}", args.commands());
+    gconf.lock().unwrap().raw = args.raw;
+    gconf.lock().unwrap().filter = args.filter.clone();
+    gconf.lock().unwrap().columns = args.columns.as_deref().map(cmd::ColumnSpec::parse_list);
+    gconf.lock().unwrap().key = args.key.clone();
+    gconf.lock().unwrap().rates = args.rate.clone();
+    gconf.lock().unwrap().aggregates = args.footer.clone();
+    gconf.lock().unwrap().group_by = args.group_by.clone();
+    gconf.lock().unwrap().cli_alerts = args.alert.clone();
+    gconf.lock().unwrap().alert_on = args.alert_on.clone();
+    gconf.lock().unwrap().alert_off = args.alert_off.clone();
+    gconf.lock().unwrap().exit_on_match = args.exit_on_match;
+    gconf.lock().unwrap().chgexit = args.chgexit;
+    gconf.lock().unwrap().timeout = args.timeout.filter(|&secs| secs > 0);
+    gconf.lock().unwrap().on_change = args.on_change.clone();
+    gconf.lock().unwrap().on_alert = args.on_alert.clone();
+    let secret = args.webhook_secret.clone().or_else(|| std::env::var("VIGILANT_WEBHOOK_SECRET").ok());
+    gconf.lock().unwrap().cli_webhooks = args.webhook.iter()
+        .map(|url| webhook::WebhookConfig { url: url.clone(), secret: secret.clone(), events: Vec::new() })
+        .collect();
+    // Bad URLs are reported before any window opens
+    for config in gconf.lock().unwrap().webhook_configs() {
+        if let Err(e) = config.validate() {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    gconf.lock().unwrap().cli_notify = args.notify;
+    // Bad patterns are reported before any window opens
+    if let Err(e) = gconf.lock().unwrap().line_triggers() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    gconf.lock().unwrap().cli_interval = args.interval;
+    gconf.lock().unwrap().cli_history = args.history;
+    gconf.lock().unwrap().cli_no_store = args.no_store;
+    gconf.lock().unwrap().cli_store_dir = args.store_dir.clone();
+    gconf.lock().unwrap().cli_keep_runs = args.keep_runs;
+    gconf.lock().unwrap().cli_keep_age = args.keep_age.clone();
+    gconf.lock().unwrap().cli_keep_size = args.keep_size.clone();
+    if let Some(ref text) = args.layout {
+        match layout::LayoutMode::parse(text) {
+            Ok(mode) => gconf.lock().unwrap().cli_layout_mode = Some(mode),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            },
+        }
+    }
+    // A layout broken by hand falls back to tabs rather than confusing the UI
+    let bad_layout = gconf.lock().unwrap().layout.validate();
+    if let Err(e) = bad_layout {
+        eprintln!("Ignoring dashboard layout: {}", e);
+        gconf.lock().unwrap().layout = layout::Layout::default();
+    }
+    if let Some(speed) = args.speed {
+        if !(speed > 0.0 && speed.is_finite()) {
+            eprintln!("Bad replay speed {}", speed);
+            process::exit(1);
+        }
+        gconf.lock().unwrap().speed = speed;
+    }
+    // The recording is read once, every client replays it from the start
+    let session = match args.replay {
+        Some(ref path) => match session::read(path) {
+            Ok(session) => {
+                let mut conf = gconf.lock().unwrap();
+                conf.set_cmdline(&session.header.cmdline);
+                conf.apply_session_config(&session.header.cmdline, &session.header.config);
+                Some(Arc::new(session))
+            },
+            Err(e) => {
+                eprintln!("Cannot replay: {}", e);
+                process::exit(1);
+            },
+        },
+        None => None,
+    };
+    // A recording has a single command, the one replayed
+    let commands = match session {
+        Some(ref session) => vec![session.header.cmdline.clone()],
+        None => args.commands(),
+    };
+    {
+        let mut conf = gconf.lock().unwrap();
+        conf.resolve_watches(&commands);
+        let first = conf.active[0].cmdline.clone();
+        conf.set_cmdline(&first);
+    }
+    // Only the first watch is recorded
+    let recorder = match args.record {
+        Some(ref path) => {
+            let conf = gconf.lock().unwrap();
+            let first = &conf.active[0];
+            let interval = first.interval.unwrap_or_else(|| conf.interval());
+            match session::Recorder::create(path, &conf.get_cmdline(), interval, conf.session_config(first)) {
+                Ok(recorder) => Some(Arc::new(Mutex::new(recorder))),
+                Err(e) => {
+                    eprintln!("Cannot record: {}", e);
+                    process::exit(1);
+                },
+            }
+        },
+        None => None,
+    };
+    tauri::Builder::default()
+        .plugin(tauri_plugin_notification::init())
+        .setup(move |app| {
+            let gconf_clone = Arc::clone(&gconf);
+            // A recording played back is not news
+            let notifier = {
+                let conf = gconf.lock().unwrap();
+                match (conf.notify(), &session) {
+                    (true, None) => Some(Arc::new(Mutex::new(
+                        notify::Notifier::new(app.handle().clone(), Duration::from_secs(conf.notify_gap))))),
+                    _ => None,
+                }
+            };
+            tokio::spawn(async move {
+                start_websocket_server(gconf_clone, session, recorder, notifier).await;
+            });
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("Error while running tauri application");
+}
+
+use tokio_tungstenite::WebSocketStream;
+use futures_util::stream::SplitSink;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+type WsSink = SplitSink<WebSocketStream<tokio::net::TcpStream>, Message>;
+
+/// Where the messages about a watch go: to every client following it, or
+/// to the one client a reply is meant for.
+#[derive(Clone)]
+enum Outbox {
+    All(broadcast::Sender<String>),
+    One(mpsc::UnboundedSender<String>),
+}
+
+/// The outbox of a watch, with the watch the messages sent are about.
+struct WatchWriter<'a> {
+    out: &'a Outbox,
+    watch: &'a str,
+}
+
+/// A message about one watch, shown by the UI in the tab of that watch.
+#[derive(Serialize)]
+struct Addressed<'a, T> {
+    watch: &'a str,
+    #[serde(flatten)]
+    message: T,
+}
+
+pub async fn send_json_message<T>(
+    writer: &mut WsSink,
+    rdata: T,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: Serialize,
+{
+    // Serialize the data to JSON
+    let json_message = serde_json::to_string(&rdata).map_err(|e| {
+        eprintln!("Serialization error: {}", e);
+        e
+    })?;
+
+    // Send the message
+    writer.send(Message::Text(json_message.clone())).await.map_err(|e| {
+        eprintln!("Error sending message: {}", e);
+        e
+    })?;
+
+    println!("Sent message: {}", json_message);
+    Ok(())
+}
+
+/// Sends a message about the watch of the writer. Clients which went away
+/// are not an error, the runner of the watch goes on without them.
+async fn send_watch_message<T: Serialize>(
+    writer: &mut WatchWriter<'_>,
+    rdata: T,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let watch = writer.watch;
+    let json_message = serde_json::to_string(&Addressed { watch, message: rdata }).map_err(|e| {
+        eprintln!("Serialization error: {}", e);
+        e
+    })?;
+    println!("Sent message: {}", json_message);
+    match writer.out {
+        Outbox::All(ref updates) => { let _ = updates.send(json_message); },
+        Outbox::One(ref client) => { let _ = client.send(json_message); },
+    }
+    Ok(())
+}
+
+async fn start_websocket_server(
+    gconf: Arc<Mutex<GConf>>,
+    session: Option<Arc<session::Session>>,
+    recorder: Option<Arc<Mutex<session::Recorder>>>,
+    notifier: Option<Arc<Mutex<notify::Notifier>>>,
+) {
+    // Shared by every runner, so that events are delivered in order
+    let webhooks: Vec<webhook::Webhook> = gconf.lock().unwrap().webhook_configs().into_iter()
+        .filter_map(|config| match webhook::Webhook::start(config) {
+            Ok(webhook) => Some(webhook),
+            Err(e) => {
+                eprintln!("Ignoring webhook: {}", e);
+                None
+            },
+        })
+        .collect();
+    let recorded = gconf.lock().unwrap().active[0].id.clone();
+    let (changes, _) = broadcast::channel(CHANGES);
+    let runners = Arc::new(Mutex::new(HashMap::new()));
+    let shared = Shared { gconf, session, recorder, recorded, notifier, webhooks, changes, runners };
+
+    // The watches run whether a client looks at them or not
+    shared.restart(&[]);
+
+    let addr = "127.0.0.1:8080".parse::<SocketAddr>().unwrap();
+    let listener = TcpListener::bind(&addr).await.unwrap();
+    println!("WebSocket server listening on {}", addr);
+
+    // Clients are served together, so that watches changed by one are
+    // changed for all of them
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(serve_client(stream, shared.clone()));
+    }
+}
+
+/// Watch changes waiting for a client, more make it follow all its watches
+/// again.
+const CHANGES: usize = 64;
+
+/// Messages about a watch waiting for a client, more make it start over
+/// from the whole of the watch.
+const UPDATES: usize = 1024;
+
+/// A change made by one client which every client has to show.
+#[derive(Clone, Debug)]
+enum Change {
+    Watch(String),  // Id of a watch added, changed or removed
+    Watches,        // Table settings of a watch changed, it keeps running
+    Layout,
+}
+
+/// What the clients share: the settings, the recording, where events go,
+/// the runners of the watches, and the changes made by any of them.
+#[derive(Clone)]
+struct Shared {
+    gconf: Arc<Mutex<GConf>>,
+    session: Option<Arc<session::Session>>,
+    recorder: Option<Arc<Mutex<session::Recorder>>>,
+    recorded: String,  // Id of the watch being recorded
+    notifier: Option<Arc<Mutex<notify::Notifier>>>,
+    webhooks: Vec<webhook::Webhook>,
+    changes: broadcast::Sender<Change>,
+    runners: Arc<Mutex<HashMap<String, Runner>>>,  // By watch id
+}
+
+/// The task running a watch once for every client. Dropping the runner
+/// closes its requests, and the task ends once the run under way is over.
+struct Runner {
+    requests: mpsc::UnboundedSender<Request>,
+    task: JoinHandle<()>,
+}
+
+/// What clients ask of the runner of a watch.
+enum Request {
+    /// Sends the whole of the watch to the client, then hands back what
+    /// changes from there on.
+    Follow {
+        client: mpsc::UnboundedSender<String>,
+        reply: oneshot::Sender<broadcast::Receiver<String>>,
+    },
+    /// A message from the UI about the watch, replies go to the client.
+    Message {
+        text: String,
+        client: mpsc::UnboundedSender<String>,
+    },
+}
+
+impl Shared {
+    /// Brings the runners in line with the watch definitions: those of the
+    /// watches given and of the watches removed are stopped, and watches
+    /// without a runner get one.
+    fn restart(&self, ids: &[String]) {
+        let defs = self.gconf.lock().unwrap().active.clone();
+        let mut runners = self.runners.lock().unwrap();
+        let stopped: Vec<String> = runners.keys()
+            .filter(|id| ids.contains(id) || !defs.iter().any(|def| def.id == **id))
+            .cloned()
+            .collect();
+        let mut previous = HashMap::new();
+        for id in stopped {
+            // The requests are closed here, the task is left to end
+            if let Some(runner) = runners.remove(&id) {
+                previous.insert(id, runner.task);
+            }
+        }
+        for def in defs {
+            if !runners.contains_key(&def.id) {
+                let previous = previous.remove(&def.id);
+                runners.insert(def.id.clone(), self.start(def, previous));
+            }
+        }
+    }
+
+    /// Starts running a watch, once the runner it replaces is done and has
+    /// let go of the run store.
+    fn start(&self, def: WatchDef, previous: Option<JoinHandle<()>>) -> Runner {
+        let actions = {
+            let conf = self.gconf.lock().unwrap();
+            // Replayed runs are played to the UI only
+            let live = self.session.is_none();
+            RunActions {
+                exit_on_match: conf.exit_on_match && live,
+                chgexit: conf.chgexit && live,
+                notifier: self.notifier.clone(),
+                on_change: conf.on_change.clone().filter(|_| live),
+                on_alert: conf.on_alert.clone().filter(|_| live),
+                webhooks: if live { self.webhooks.clone() } else { Vec::new() },
+            }
+        };
+        // A recording has a single command line, it stops when the watch
+        // recorded runs another one
+        let recorder = self.recorder.clone()
+            .filter(|_| def.id == self.recorded && def.cmdline == self.gconf.lock().unwrap().get_cmdline());
+        let gconf = Arc::clone(&self.gconf);
+        let session = self.session.clone();
+        let changes = self.changes.clone();
+        let (requests, inbox) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            let watch = Watch::open(def, &gconf, session.as_deref(), recorder.as_ref(), actions, changes).await;
+            watch.run(inbox, &gconf).await;
+        });
+        Runner { requests, task }
+    }
+
+    /// Hands a request to the runner of a watch, false when there is none.
+    fn request(&self, id: &str, request: Request) -> bool {
+        let runners = self.runners.lock().unwrap();
+        runners.get(id).is_some_and(|runner| runner.requests.send(request).is_ok())
+    }
+
+    /// Forwards what happens to a watch to a client, starting with the
+    /// whole of it, and again whenever the client falls behind.
+    fn follow(&self, id: String, client: mpsc::UnboundedSender<String>) -> JoinHandle<()> {
+        let shared = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (reply, updates) = oneshot::channel();
+                if !shared.request(&id, Request::Follow { client: client.clone(), reply }) {
+                    return;
+                }
+                let Ok(mut updates) = updates.await else {
+                    return;
+                };
+                loop {
+                    match updates.recv().await {
+                        Ok(text) => {
+                            if client.send(text).is_err() {
+                                return;
+                            }
+                        },
+                        Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Err(broadcast::error::RecvError::Closed) => return,
+                    }
+                }
+            }
+        })
+    }
+}
+
+async fn serve_client(stream: tokio::net::TcpStream, shared: Shared) {
+    let ws_stream = accept_async(stream).await.unwrap();
+    println!("New WebSocket connection");
+
+    let (mut sink, mut reader) = ws_stream.split();
+    let mut changes = shared.changes.subscribe();
+    let gconf = &shared.gconf;
+
+    // Sending the welcome message
+    let welcome_message = WelcomeMessage {
+        message: "Welcome to the WebSocket server!".to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let json_message = serde_json::to_string(&welcome_message).unwrap();
+    //writer.send(Message::Text(json_message)).await.unwrap();
+    if let Err(e) = sink.send(Message::Text(json_message)).await {
+        // Handle the error (e.g., logging)
+        eprintln!("Error sending message: {}", e);
+        return;
+    }
+
+    // The tabs, every following message is about one of them
+    let (out, mut outgoing) = mpsc::unbounded_channel();
+    let mut followed = HashMap::new();
+    refollow(&shared, &mut sink, &mut followed, &out, &[]).await;
+
+    loop {
+        tokio::select! {
+            Some(text) = outgoing.recv() => {
+                if let Err(e) = sink.send(Message::Text(text)).await {
+                    eprintln!("Error sending message: {}", e);
+                    break;
+                }
+            }
+            change = changes.recv() => {
+                let ids: Vec<String> = match change {
+                    Ok(Change::Watch(id)) => vec![id],
+                    Ok(Change::Watches) => {
+                        let defs = gconf.lock().unwrap().active.clone();
+                        send_watches(&mut sink, defs).await;
+                        continue;
+                    },
+                    Ok(Change::Layout) => {
+                        let layout = gconf.lock().unwrap().layout();
+                        send_layout(&mut sink, layout).await;
+                        continue;
+                    },
+                    // Too many changes to follow, start every watch over
+                    Err(broadcast::error::RecvError::Lagged(_)) => followed.keys().cloned()
+                        .chain(gconf.lock().unwrap().active.iter().map(|def| def.id.clone()))
+                        .collect(),
+                    Err(broadcast::error::RecvError::Closed) => Vec::new(),
+                };
+                refollow(&shared, &mut sink, &mut followed, &out, &ids).await;
+            }
+            msg = reader.next() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(msg) = serde_json::from_str::<WindowMessage>(&text) {
+                            if let Err(e) = handle_window_message(msg, &shared).await {
+                                eprintln!("Failed to handle window message: {}", e);
+                                send_error(&mut sink, e).await;
+                            }
+                            continue;
+                        }
+                        let target: Target = serde_json::from_str(&text).unwrap_or_default();
+                        let id = match target.watch {
+                            Some(id) => id,
+                            None => gconf.lock().unwrap().active.first().map(|def| def.id.clone()).unwrap_or_default(),
+                        };
+                        let request = Request::Message { text, client: out.clone() };
+                        if !shared.request(&id, request) {
+                            send_error(&mut sink, format!("No watch {}", id)).await;
+                        }
+                    },
+                    Some(Ok(Message::Close(_))) | None => {
+                        println!("WebSocket connection closed");
+                        break;
+                    },
+                    Some(Ok(_)) => {},
+                    Some(Err(e)) => {
+                        eprintln!("WebSocket error: {}", e);
+                        break;
+                    },
+                }
+            }
+        }
+    }
+    for (_, task) in followed {
+        task.abort();
+    }
+}
+
+/// Brings what a client follows in line with the watch definitions after
+/// some were added, changed or removed: those watches are followed anew,
+/// from their new runner, the others go on.
+async fn refollow(
+    shared: &Shared,
+    sink: &mut WsSink,
+    followed: &mut HashMap<String, JoinHandle<()>>,
+    out: &mpsc::UnboundedSender<String>,
+    ids: &[String],
+) {
+    let defs = shared.gconf.lock().unwrap().active.clone();
+    send_watches(sink, defs.clone()).await;
+    let layout = shared.gconf.lock().unwrap().layout();
+    send_layout(sink, layout).await;
+
+    // Nothing more is forwarded from the runners let go of
+    let stale: Vec<String> = followed.keys()
+        .filter(|id| ids.contains(id) || !defs.iter().any(|def| def.id == **id))
+        .cloned()
+        .collect();
+    for id in stale {
+        if let Some(task) = followed.remove(&id) {
+            task.abort();
+            let _ = task.await;
+        }
+    }
+    for def in defs {
+        followed.entry(def.id.clone())
+            .or_insert_with(|| shared.follow(def.id, out.clone()));
+    }
+}
+
+async fn send_watches(sink: &mut WsSink, watches: Vec<WatchDef>) {
+    let w = SetWatches {
+        command: "SetWatches".to_string(),
+        watches,
+    };
+    if let Err(e) = send_json_message(sink, w).await {
+        eprintln!("Failed to send watches: {}", e);
+    }
+}
+
+/// Reports an error which is not about any watch to the UI.
+async fn send_error(sink: &mut WsSink, message: String) {
+    let err = ErrorMessage {
+        command: "Error".to_string(),
+        message,
+    };
+    if let Err(e) = send_json_message(sink, err).await {
+        eprintln!("Failed to send error: {}", e);
+    }
+}
+
+async fn send_layout(sink: &mut WsSink, layout: layout::Layout) {
+    let l = SetLayout {
+        command: "SetLayout".to_string(),
+        layout,
+    };
+    if let Err(e) = send_json_message(sink, l).await {
+        eprintln!("Failed to send layout: {}", e);
+    }
+}
+
+/// Applies a message about the window, returning an error text to be
+/// reported back to the UI. Changes to the watches restart their runner,
+/// and changes reach every client through the change channel, this one
+/// included.
+async fn handle_window_message(
+    msg: WindowMessage,
+    shared: &Shared,
+) -> Result<(), String> {
+    println!("Window message: {:?}", msg);
+    let changed = match msg {
+        WindowMessage::SetLayout { layout } => {
+            layout.validate()?;
+            let mut conf = shared.gconf.lock().unwrap();
+            conf.layout = layout;
+            // The mode picked in the UI wins over the command line from now on
+            conf.cli_layout_mode = None;
+            if let Err(e) = conf.save() {
+                eprintln!("Failed to save configuration: {}", e);
+            }
+            let _ = shared.changes.send(Change::Layout);
+            return Ok(());
+        },
+        _ if shared.session.is_some() => {
+            return Err("Watches cannot be changed while replaying a recording".to_string());
+        },
+        WindowMessage::AddWatch { definition } => shared.gconf.lock().unwrap().add_watch(definition)?,
+        WindowMessage::UpdateWatch { definition } => shared.gconf.lock().unwrap().update_watch(definition)?,
+        WindowMessage::RemoveWatch { id } => shared.gconf.lock().unwrap().remove_watch(&id)?,
+    };
+    shared.restart(std::slice::from_ref(&changed));
+    // Every client is subscribed, this one at least
+    let _ = shared.changes.send(Change::Watch(changed));
+    Ok(())
+}
+
+/// A watched command, as run for every client.
+struct Watch {
+    def: WatchDef,
+    colines: Result<cmd::CmdOutput, std::io::Error>,
+    raw: bool,
+    interval: Duration,
+    next_run: tokio::time::Instant,
+    replay: Option<session::Replay>,
+    played: tokio::time::Instant,  // When the last replayed run was played
+    actions: RunActions,
+    recorder: Option<Arc<Mutex<session::Recorder>>>,
+    title: String,
+    errors: Vec<String>,  // Why the watch could not be opened as asked
+    firing: HashMap<String, AlertFired>,  // Alerts fired and not resolved, by rule
+    updates: Outbox,  // To every client following the watch
+    changes: broadcast::Sender<Change>,  // To every client, about the settings
+}
+
+impl Watch {
+    /// Runs the command, or starts replaying the recording, and works out
+    /// the first view of its output. Nobody follows the watch yet, clients
+    /// get the whole of it when they do.
+    async fn open(
+        def: WatchDef,
+        gconf: &Arc<Mutex<GConf>>,
+        session: Option<&session::Session>,
+        recorder: Option<&Arc<Mutex<session::Recorder>>>,
+        actions: RunActions,
+        changes: broadcast::Sender<Change>,
+    ) -> Watch {
+        let (updates, _) = broadcast::channel(UPDATES);
+        let cmdline = def.cmdline.clone();
+
+        let title = match session {
+            Some(_) => format!("{} (replay)", cmdline),
+            None => cmdline.clone(),
+        };
+        println!("Cmdline: {}", cmdline);
+
+        let mut replay = None;
+        let colines = match session {
+            Some(session) => {
+                replay = Some(session::Replay::new(session, gconf.lock().unwrap().speed));
+                Ok(cmd::CmdOutput::from_run(&cmdline, session.runs[0].clone()))
+            },
+            None => {
+                let cmdline = cmdline.clone();
+                tokio::task::spawn_blocking(move || cmd::CmdOutput::new(&cmdline)).await
+                    .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+            },
+        };
+
+        let secs = def.interval.unwrap_or_else(|| gconf.lock().unwrap().interval()).max(1);
+        let interval = Duration::from_secs(secs);
+        let now = tokio::time::Instant::now();
+        let mut watch = Watch {
+            raw: def.raw,
+            def,
+            colines: Err(std::io::Error::other("not run yet")),
+            interval,
+            next_run: now + interval,
+            replay,
+            played: now,
+            actions,
+            recorder: recorder.cloned(),
+            title,
+            errors: Vec::new(),
+            firing: HashMap::new(),
+            updates: Outbox::All(updates),
+            changes,
+        };
+        watch.start(colines, gconf).await;
+        watch
+    }
+
+    /// Sets the watch up from the first run which worked, or keeps why the
+    /// command could not be run until it is tried again.
+    async fn start(&mut self, colines: Result<cmd::CmdOutput, std::io::Error>, gconf: &Arc<Mutex<GConf>>) {
+        let cmdline = self.def.cmdline.clone();
+        // Followers get what is worked out here through send_all
+        let quiet = Outbox::All(broadcast::channel(1).0);
+        let mut writer = WatchWriter { out: &quiet, watch: &self.def.id };
+        let mut errors = Vec::new();
+        let mut colines = match colines {
+            Ok(colines) => colines,
+            Err(e) => {
+                eprintln!("Cannot run {}: {}", cmdline, e);
+                notify_failure(&self.actions, &cmdline, &e, None);
+                self.errors = vec![format!("Cannot run {}: {}", cmdline, e)];
+                self.colines = Err(e);
+                return;
+            },
+        };
+
+        // Replayed runs are already kept in the recording
+        if self.replay.is_none() {
+            let store = gconf.lock().unwrap().open_store(&cmdline);
+            match store {
+                Ok(Some(store)) => {
+                    if let Err(e) = colines.set_store(store) {
+                        eprintln!("Ignoring run store: {}", e);
+                    }
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    eprintln!("Not storing runs: {}", e);
+                    errors.push(format!("Not storing the runs of {}: {}", cmdline, e));
+                },
+            }
+        }
+        if let Some(ref recorder) = self.recorder {
+            colines.set_recorder(Arc::clone(recorder));
+        }
+
+        // Patterns are looked for in the lines whether shown as a table or not
+        let triggers = gconf.lock().unwrap().line_triggers().unwrap_or_default();
+        colines.set_triggers(triggers);
+        let timeout = gconf.lock().unwrap().timeout;
+        colines.set_timeout(timeout.map(Duration::from_secs));
+
+        // Fall back to raw text when no columns can be inferred from the output
+        let def = &self.def;
+        self.raw = def.raw || !colines.is_tabular();
+
+        if !self.raw {
+            // Restore the sort remembered for this command
+            let sort = gconf.lock().unwrap().get_sort(&cmdline);
+            if let Err(e) = colines.set_sort(sort) {
+                eprintln!("Ignoring saved sort: {}", e);
+            }
+            if let Err(e) = colines.set_filter(def.filter.as_deref()) {
+                eprintln!("Ignoring filter: {}", e);
+                errors.push(format!("Ignoring filter: {}", e));
+            }
+            let limit = gconf.lock().unwrap().history();
+            colines.set_history_limit(limit);
+            if let Err(e) = colines.set_key(def.key.clone()) {
+                eprintln!("Ignoring row key: {}", e);
+                errors.push(format!("Ignoring row key: {}", e));
+            }
+            if let Err(e) = colines.set_rates(def.rates.clone()) {
+                eprintln!("Ignoring rate columns: {}", e);
+                errors.push(format!("Ignoring rate columns: {}", e));
+            }
+            if let Err(e) = colines.set_aggregates(def.aggregates.clone()) {
+                eprintln!("Ignoring footer: {}", e);
+                errors.push(format!("Ignoring footer: {}", e));
+            }
+            if let Err(e) = colines.set_group_by(def.group_by.clone()) {
+                eprintln!("Ignoring grouping: {}", e);
+                errors.push(format!("Ignoring grouping: {}", e));
+            }
+            let alerts = gconf.lock().unwrap().alert_rules();
+            if let Err(e) = colines.set_alerts(alerts) {
+                eprintln!("Ignoring alerts: {}", e);
+            }
+            if let Err(e) = colines.set_columns(def.columns.clone()) {
+                eprintln!("Ignoring column selection: {}", e);
+                errors.push(format!("Ignoring column selection: {}", e));
+            }
+            send_headers(&mut writer, &mut colines).await;
+            send_table_update(&mut writer, &mut colines).await;
+        }
+        send_alerts(&mut writer, &mut colines, &self.actions, &mut self.firing).await;
+        self.errors = errors;
+        self.colines = Ok(colines);
+    }
+
+    /// Runs the watch, serving the requests of the clients between runs,
+    /// until its runner is dropped.
+    async fn run(mut self, mut requests: mpsc::UnboundedReceiver<Request>, gconf: &Arc<Mutex<GConf>>) {
+        loop {
+            let next = self.due();
+            tokio::select! {
+                _ = tokio::time::sleep_until(next.unwrap_or_else(tokio::time::Instant::now)), if next.is_some() => {
+                    self.step(gconf).await;
+                }
+                request = requests.recv() => {
+                    match request {
+                        Some(Request::Follow { client, reply }) => {
+                            // Whatever is sent from here on comes after the whole watch
+                            let updates = match self.updates {
+                                Outbox::All(ref updates) => updates.subscribe(),
+                                Outbox::One(_) => unreachable!("watches send updates to all their clients"),
+                            };
+                            self.send_all(&Outbox::One(client)).await;
+                            let _ = reply.send(updates);
+                        },
+                        Some(Request::Message { text, client }) => self.handle(&text, client, gconf).await,
+                        None => break,
+                    }
+                }
+            }
+        }
+        println!("Stopped running {}", self.def.cmdline);
+    }
+
+    /// When the watch next has something to do, None while it has nothing:
+    /// the replay is paused or over. A command which could not be run is
+    /// tried again at every interval, as watch(1) does.
+    fn due(&self) -> Option<tokio::time::Instant> {
+        match self.replay {
+            Some(ref replay) if replay.is_ready() => Some(self.played + replay.delay()),
+            Some(_) => None,
+            None => Some(self.next_run),
+        }
+    }
+
+    /// Runs the command once, or plays the next recorded run.
+    async fn step(&mut self, gconf: &Arc<Mutex<GConf>>) {
+        let mut writer = WatchWriter { out: &self.updates, watch: &self.def.id };
+        match self.replay {
+            Some(ref mut replay) => {
+                let Ok(ref mut colines) = self.colines else {
+                    return;
+                };
+                self.played = tokio::time::Instant::now();
+                if let Some(run) = replay.next_run() {
+                    let result = colines.push_run(run);
+                    send_run(&mut writer, colines, self.raw, result, &self.actions, &mut self.firing).await;
+                }
+                send_replay_state(&mut writer, replay).await;
+            },
+            None => {
+                // Runs missed while a slow command ran are skipped
+                self.next_run = (self.next_run + self.interval).max(tokio::time::Instant::now());
+                println!("{} seconds have passed", self.interval.as_secs());
+                // The command blocks, so it runs away from the clients and
+                // the other watches
+                let cmdline = self.def.cmdline.clone();
+                let mut colines = match std::mem::replace(&mut self.colines, Err(std::io::Error::other("running"))) {
+                    Ok(colines) => colines,
+                    Err(_) => {
+                        let retried = tokio::task::spawn_blocking(move || cmd::CmdOutput::new(&cmdline)).await
+                            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                        self.start(retried, gconf).await;
+                        // Followers get the watch whole once it runs, or why it still does not
+                        let out = self.updates.clone();
+                        if self.colines.is_ok() {
+                            self.send_all(&out).await;
+                        } else {
+                            let mut writer = WatchWriter { out: &out, watch: &self.def.id };
+                            for message in &self.errors {
+                                let err = ErrorMessage {
+                                    command: "Error".to_string(),
+                                    message: message.clone(),
+                                };
+                                if let Err(e) = send_watch_message(&mut writer, err).await {
+                                    eprintln!("Failed to send error: {}", e);
+                                }
+                            }
+                        }
+                        return;
+                    },
+                };
+                let ran = tokio::task::spawn_blocking(move || {
+                    let result = colines.execute(&cmdline);
+                    (colines, result)
+                }).await;
+                let (mut colines, result) = match ran {
+                    Ok(ran) => ran,
+                    Err(e) => {
+                        eprintln!("Stopped running {}: {}", self.def.cmdline, e);
+                        self.colines = Err(std::io::Error::other(e));
+                        return;
+                    },
+                };
+                send_run(&mut writer, &mut colines, self.raw, result, &self.actions, &mut self.firing).await;
+                self.colines = Ok(colines);
+            },
+        }
+    }
+
+    /// Sends the whole of the watch to a client starting to follow it, or
+    /// to every follower when it starts over.
+    async fn send_all(&mut self, out: &Outbox) {
+        let mut writer = WatchWriter { out, watch: &self.def.id };
+
+        // Sending the title message
+        let title_message = SetTitle {
+            command: "SetTitle".to_string(),
+            center: self.title.clone(),
+        };
+        println!("title msg : {:?}", title_message);
+        if let Err(e) = send_watch_message(&mut writer, title_message).await {
+            eprintln!("Failed to send title message: {}", e);
+        }
+        for message in &self.errors {
+            let err = ErrorMessage {
+                command: "Error".to_string(),
+                message: message.clone(),
+            };
+            if let Err(e) = send_watch_message(&mut writer, err).await {
+                eprintln!("Failed to send error: {}", e);
+            }
+        }
+
+        if let Ok(ref mut colines) = self.colines {
+            if self.raw {
+                let t = SetText {
+                    command: "SetText".to_string(),
+                    lines: colines.get_text(),
+                    changed: Vec::new(),
+                };
+                if let Err(e) = send_watch_message(&mut writer, t).await {
+                    eprintln!("Failed to send text: {}", e);
+                }
+            } else {
+                send_headers(&mut writer, colines).await;
+                send_table(&mut writer, colines).await;
+            }
+            for fired in self.firing.values() {
+                if let Err(e) = send_watch_message(&mut writer, fired).await {
+                    eprintln!("Failed to send alert: {}", e);
+                }
+            }
+            send_timeline(&mut writer, colines).await;
+        }
+        if let Some(ref replay) = self.replay {
+            send_replay_state(&mut writer, replay).await;
+        }
+    }
+
+    /// Applies a message from the UI, reporting errors back to the client
+    /// it came from.
+    async fn handle(&mut self, text: &str, client: mpsc::UnboundedSender<String>, gconf: &Arc<Mutex<GConf>>) {
+        let out = Outbox::One(client);
+        let mut reply = WatchWriter { out: &out, watch: &self.def.id };
+        let mut writer = WatchWriter { out: &self.updates, watch: &self.def.id };
+        let was_paused = self.replay.as_ref().is_some_and(|r| r.is_paused());
+        if let Ok(ref mut colines) = self.colines {
+            if let Err(e) = handle_client_message(text, &mut writer, &mut reply, gconf, &self.changes, colines, &mut self.replay).await {
+                eprintln!("Failed to handle client message: {}", e);
+                let err = ErrorMessage {
+                    command: "Error".to_string(),
+                    message: e,
+                };
+                if let Err(e) = send_watch_message(&mut reply, err).await {
+                    eprintln!("Failed to send error: {}", e);
+                }
+            }
+        }
+        // Resuming waits a full step rather than catching up on the pause
+        if was_paused && self.replay.as_ref().is_some_and(|r| !r.is_paused()) {
+            self.played = tokio::time::Instant::now();
+        }
+    }
+}
+
+/// What to do about a run besides showing it, the same for every client.
+struct RunActions {
+    exit_on_match: bool,  // Exit once an alert fires
+    chgexit: bool,        // Exit when the output changes, notifying first
+    notifier: Option<Arc<Mutex<notify::Notifier>>>,
+    on_change: Option<String>,  // Hook run when the output changes
+    on_alert: Option<String>,   // Hook run when an alert fires or resolves
+    webhooks: Vec<webhook::Webhook>,
+}
+
+impl RunActions {
+    fn notify(&self, title: &str, body: &str) {
+        if let Some(ref notifier) = self.notifier {
+            notifier.lock().unwrap().notify(title, body);
+        }
+    }
+
+    /// Whether anything listens to changes of the output.
+    fn wants_changes(&self) -> bool {
+        self.on_change.is_some() || !self.webhooks.is_empty()
+    }
+
+    /// Hands an event about the latest run to its hook command, if any, and
+    /// to the webhooks.
+    fn dispatch(&self, command: Option<&str>, event: hook::HookEvent, colines: &cmd::CmdOutput) {
+        let latest = colines.latest_run();
+        let run = hook::HookRun {
+            cmdline: colines.cmdline.clone(),
+            run_id: latest.map(|run| run.id),
+            timestamp: latest.map(|run| run.timestamp),
+            status: latest.and_then(|run| run.status),
+        };
+        if let Some(command) = command {
+            hook::spawn(command, &event, &run);
+        }
+        if self.webhooks.is_empty() {
+            return;
+        }
+        match hook::payload(&event, &run) {
+            Ok(body) => {
+                for webhook in &self.webhooks {
+                    webhook.post(event.name(), &body);
+                }
+            },
+            Err(e) => eprintln!("Cannot describe the {} event: {}", event.name(), e),
+        }
+    }
+}
+
+/// Applies the outcome of a run, live or replayed, and sends what changed.
+async fn send_run(
+    writer: &mut WatchWriter<'_>,
+    colines: &mut cmd::CmdOutput,
+    raw: bool,
+    result: Result<Vec<String>, std::io::Error>,
+    actions: &RunActions,
+    firing: &mut HashMap<String, AlertFired>,
+) {
+    match result {
+        Ok(oplines) => {
+            println!("No of output lines {}", oplines.len());
+            let changed = colines.update_lines(oplines.clone()).expect("update bad");
+            if actions.chgexit && !changed.is_empty() {
+                let first = changed.iter().filter_map(|&i| oplines.get(i)).next();
+                let body = match first {
+                    Some(line) => format!("{} lines changed, first: {}", changed.len(), line),
+                    None => format!("{} lines removed", changed.len()),
+                };
+                actions.notify(&format!("{} changed", colines.cmdline), &body);
+                println!("Output of {} changed, exiting", colines.cmdline);
+                process::exit(0);
+            }
+            if actions.wants_changes() && !changed.is_empty() {
+                let event = hook::HookEvent::Change {
+                    lines: changed.iter().filter_map(|&i| oplines.get(i).cloned()).collect(),
+                    diff: if raw { None } else { colines.latest_diff() },
+                };
+                actions.dispatch(actions.on_change.as_deref(), event, colines);
+            }
+            if raw {
+                // watch(1) redraws the whole screen, so send every line
+                let t = SetText {
+                    command: "SetText".to_string(),
+                    lines: oplines,
+                    changed,
+                };
+                if let Err(e) = send_watch_message(writer, t).await {
+                    eprintln!("Failed to send text: {}", e);
+                }
+            } else {
+                send_table_update(writer, colines).await;
+            }
+            send_alerts(writer, colines, actions, firing).await;
+        },
+        Err(e) => {
+            println!("Error executing command {:?}", e);
+            // The first line of stderr usually tells why
+            let reason = colines.latest_run().and_then(|run| run.stderr.first().cloned());
+            notify_failure(actions, &colines.cmdline, &e, reason);
+        },
+    }
+    // Failed runs are kept as well, so the timeline always moves
+    send_timeline(writer, colines).await;
+}
+
+/// Notifies that a run failed or timed out, with the reason when known.
+fn notify_failure(actions: &RunActions, cmdline: &str, e: &std::io::Error, reason: Option<String>) {
+    let title = match e.kind() {
+        std::io::ErrorKind::TimedOut => format!("{} timed out", cmdline),
+        _ => format!("{} failed", cmdline),
+    };
+    let body = match reason {
+        Some(line) => format!("{}: {}", e, line),
+        None => e.to_string(),
+    };
+    actions.notify(&title, &body);
+}
+
+/// Sends the column headers along with the current table settings.
+async fn send_headers(
+    writer: &mut WatchWriter<'_>,
+    colines: &mut cmd::CmdOutput,
+) {
+    if let Err(e) = colines.get_headers() {
+        eprintln!("Error getting headers: {}", e);
+        return;
+    }
+    let available = colines.table_headers();
+    let (names, labels, kinds) = colines.get_view_headers();
+    println!("Headers: {:?}", labels);
+    let hdr_message = SetHeaders {
+        command: "SetHeaders".to_string(),
+        headers: labels,
+        names,
+        available,
+        kinds,
+        sort: colines.get_sort(),
+        filter: colines.get_filter(),
+        rates: colines.get_rates(),
+        aggregates: colines.get_aggregates(),
+        group_by: colines.get_group_by(),
+        alerts: colines.get_alerts(),
+    };
+    if let Err(e) = send_watch_message(writer, hdr_message).await {
+        eprintln!("Failed to send headers: {}", e);
+    }
+
+    // The history kept so far, later runs only send their new points
+    let h = SetCellHistory {
+        command: "SetCellHistory".to_string(),
+        limit: colines.history_limit(),
+        rows: colines.get_cell_history(),
+    };
+    if let Err(e) = send_watch_message(writer, h).await {
+        eprintln!("Failed to send cell history: {}", e);
+    }
+}
+
+/// Sends the table rows that changed since the last update, the new row
+/// count when rows were added or removed, and the history of the cells.
+async fn send_table_update(
+    writer: &mut WatchWriter<'_>,
+    colines: &mut cmd::CmdOutput,
+) {
+    let before = colines.row_count();
+    let changed = colines.update_view();
+
+    // Grouped tables are sent whole, as groups are few and move around
+    if let Some(column) = colines.get_group_by() {
+        let g = SetGroups {
+            command: "SetGroups".to_string(),
+            column,
+            groups: colines.get_groups(),
+        };
+        if let Err(e) = send_watch_message(writer, g).await {
+            eprintln!("Failed to send groups: {}", e);
+        }
+        send_footer(writer, colines).await;
+        return;
+    }
+
+    send_rows(writer, colines, changed).await;
+    if colines.row_count() != before {
+        let r = SetRowCount {
+            command: "SetRowCount".to_string(),
+            count: colines.row_count(),
+        };
+        if let Err(e) = send_watch_message(writer, r).await {
+            eprintln!("Failed to send row count: {}", e);
+        }
+    }
+
+    send_footer(writer, colines).await;
+
+    // Every run adds a value to each numeric cell, sent all at once
+    let (time, rows) = colines.get_latest_cells();
+    let h = AppendCellHistory {
+        command: "AppendCellHistory".to_string(),
+        limit: colines.history_limit(),
+        time,
+        rows,
+    };
+    if let Err(e) = send_watch_message(writer, h).await {
+        eprintln!("Failed to send cell history: {}", e);
+    }
+}
+
+/// Sends the whole table as last updated, to a client which has none of it.
+async fn send_table(
+    writer: &mut WatchWriter<'_>,
+    colines: &mut cmd::CmdOutput,
+) {
+    if let Some(column) = colines.get_group_by() {
+        let g = SetGroups {
+            command: "SetGroups".to_string(),
+            column,
+            groups: colines.get_groups(),
+        };
+        if let Err(e) = send_watch_message(writer, g).await {
+            eprintln!("Failed to send groups: {}", e);
+        }
+    } else {
+        send_rows(writer, colines, (0..colines.row_count()).collect()).await;
+        let r = SetRowCount {
+            command: "SetRowCount".to_string(),
+            count: colines.row_count(),
+        };
+        if let Err(e) = send_watch_message(writer, r).await {
+            eprintln!("Failed to send row count: {}", e);
+        }
+    }
+    send_footer(writer, colines).await;
+}
+
+/// Sends the rows of the view at the given indices.
+async fn send_rows(
+    writer: &mut WatchWriter<'_>,
+    colines: &cmd::CmdOutput,
+    indices: Vec<usize>,
+) {
+    for idx in indices {
+        let (values, numbers) = match colines.get_row(idx) {
+            Some(row) => row,
+            None => continue, // The row went away with this update
+        };
+        let r = SetDataRow {
+            command: "SetDataRow".to_string(),
+            index: idx as i32,
+            key: colines.get_row_key(idx).unwrap_or_default(),
+            values,
+            numbers,
+        };
+        if let Err(e) = send_watch_message(writer, r).await {
+            eprintln!("Failed to send data row: {}", e);
+        }
+    }
+}
+
+/// Sends the footer aggregates, which follow the filter and so can change
+/// with any update.
+async fn send_footer(
+    writer: &mut WatchWriter<'_>,
+    colines: &mut cmd::CmdOutput,
+) {
+    let footer = colines.get_footer();
+    if !footer.is_empty() || !colines.get_aggregates().is_empty() {
+        let f = SetFooter {
+            command: "SetFooter".to_string(),
+            cells: footer,
+        };
+        if let Err(e) = send_watch_message(writer, f).await {
+            eprintln!("Failed to send footer: {}", e);
+        }
+    }
+}
+
+/// Checks the alert rules and triggers against the latest run and sends the
+/// alerts which fired or resolved, with a notification for those firing.
+/// With --exit-on-match, the first alert firing is printed and ends the
+/// program. The alerts still firing are kept for clients which follow the
+/// watch later.
+async fn send_alerts(
+    writer: &mut WatchWriter<'_>,
+    colines: &mut cmd::CmdOutput,
+    actions: &RunActions,
+    firing: &mut HashMap<String, AlertFired>,
+) {
+    let events = colines.check_alerts();
+    if events.is_empty() {
+        return;
+    }
+    let headers = colines.table_headers();
+    for event in events {
+        let result = match event {
+            alert::AlertEvent::Fired { rule, rows } => {
+                println!("Alert fired: {} ({} rows)", rule, rows.len());
+                if actions.exit_on_match {
+                    for row in &rows {
+                        println!("{}: {}", rule, row.values.join(" "));
+                    }
+                    process::exit(0);
+                }
+                let mut keys: Vec<&str> = rows.iter().take(5).map(|row| row.key.as_str()).collect();
+                if rows.len() > 5 {
+                    keys.push("...");
+                }
+                let body = match rows.len() {
+                    0 => "No line matches any more".to_string(),
+                    n => format!("{} rows: {}", n, keys.join(", ")),
+                };
+                actions.notify(&format!("Alert: {}", rule), &body);
+                let event = hook::HookEvent::Alert {
+                    rule: rule.clone(),
+                    state: "fired",
+                    headers: headers.clone(),
+                    rows: rows.clone(),
+                };
+                actions.dispatch(actions.on_alert.as_deref(), event, colines);
+                let fired = AlertFired {
+                    command: "AlertFired".to_string(),
+                    rule: rule.clone(),
+                    headers: headers.clone(),
+                    rows,
+                };
+                firing.insert(rule, fired.clone());
+                send_watch_message(writer, fired).await
+            },
+            alert::AlertEvent::Resolved { rule } => {
+                println!("Alert resolved: {}", rule);
+                let event = hook::HookEvent::Alert {
+                    rule: rule.clone(),
+                    state: "resolved",
+                    headers: headers.clone(),
+                    rows: Vec::new(),
+                };
+                actions.dispatch(actions.on_alert.as_deref(), event, colines);
+                firing.remove(&rule);
+                send_watch_message(writer, AlertResolved {
+                    command: "AlertResolved".to_string(),
+                    rule,
+                }).await
+            },
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to send alert: {}", e);
+        }
+    }
+}
+
+/// Sends the range of runs the UI can go back to.
+async fn send_timeline(
+    writer: &mut WatchWriter<'_>,
+    colines: &cmd::CmdOutput,
+) {
+    if let Some((first, last)) = colines.get_run_range() {
+        let t = SetTimeline {
+            command: "SetTimeline".to_string(),
+            first,
+            last,
+        };
+        if let Err(e) = send_watch_message(writer, t).await {
+            eprintln!("Failed to send timeline: {}", e);
+        }
+    }
+}
+
+async fn send_replay_state(
+    writer: &mut WatchWriter<'_>,
+    replay: &session::Replay,
+) {
+    let r = SetReplayState {
+        command: "SetReplayState".to_string(),
+        state: replay.state(),
+    };
+    if let Err(e) = send_watch_message(writer, r).await {
+        eprintln!("Failed to send replay state: {}", e);
+    }
+}
+
+/// Applies a message received from the UI, returning an error text to be
+/// reported back to it. Changes to the view go to every client, answers to
+/// the one which asked.
+/// Changes a table setting of a watch from the UI, saving it with the
+/// watches and showing it to every client.
+fn change_watch(
+    gconf: &Arc<Mutex<GConf>>,
+    changes: &broadcast::Sender<Change>,
+    id: &str,
+    change: impl FnOnce(&mut WatchDef),
+) {
+    let mut conf = gconf.lock().unwrap();
+    if let Some(def) = conf.watch_mut(id) {
+        change(def);
+        conf.watches_changed();
+        // Every client is subscribed, the one which made the change at least
+        let _ = changes.send(Change::Watches);
+    }
+}
+
+async fn handle_client_message(
+    text: &str,
+    writer: &mut WatchWriter<'_>,
+    reply: &mut WatchWriter<'_>,
+    gconf: &Arc<Mutex<GConf>>,
+    changes: &broadcast::Sender<Change>,
+    colines: &mut cmd::CmdOutput,
+    replay: &mut Option<session::Replay>,
+) -> Result<(), String> {
+    let msg: ClientMessage = serde_json::from_str(text)
+        .map_err(|e| format!("Bad message {}: {}", text, e))?;
+    println!("Client message: {:?}", msg);
+    let cmdline = colines.cmdline.clone();
+
+    // Whether the table rows have to be sent again
+    let resend = match msg {
+        ClientMessage::SetSort { column, direction } => {
+            let sort = column.map(|column| cmd::SortSpec { column, direction });
+            colines.set_sort(sort.clone())?;
+            let mut conf = gconf.lock().unwrap();
+            conf.set_sort(&cmdline, sort);
+            if let Err(e) = conf.save() {
+                eprintln!("Failed to save configuration: {}", e);
+            }
+            true
+        },
+        ClientMessage::SetFilter { filter } => {
+            let filter = filter.filter(|f| !f.trim().is_empty());
+            colines.set_filter(filter.as_deref())?;
+            change_watch(gconf, changes, writer.watch, |def| def.filter = filter);
+            true
+        },
+        ClientMessage::SetColumns { columns } => {
+            colines.set_columns(columns.clone())?;
+            change_watch(gconf, changes, writer.watch, |def| def.columns = columns);
+            // Every row changes shape, start over
+            colines.reset_view();
+            send_headers(writer, colines).await;
+            true
+        },
+        ClientMessage::SetRate { column, enabled } => {
+            let mut rates = colines.get_rates();
+            rates.retain(|c| *c != column);
+            if enabled {
+                rates.push(column);
+            }
+            colines.set_rates(rates.clone())?;
+            change_watch(gconf, changes, writer.watch, |def| def.rates = rates);
+            colines.reset_view();
+            send_headers(writer, colines).await;
+            true
+        },
+        ClientMessage::SetAggregates { aggregates } => {
+            colines.set_aggregates(aggregates.clone())?;
+            change_watch(gconf, changes, writer.watch, |def| def.aggregates = aggregates);
+            send_headers(writer, colines).await;
+            true
+        },
+        ClientMessage::SetGroupBy { column } => {
+            colines.set_group_by(column.clone())?;
+            change_watch(gconf, changes, writer.watch, |def| def.group_by = column);
+            // Back to a flat table every row has to be sent again
+            colines.reset_view();
+            send_headers(writer, colines).await;
+            true
+        },
+        ClientMessage::QueryHistory { keys, column, from, to } => {
+            let series = colines.query_history(&keys, &column, from, to)?;
+            let r = HistoryResult {
+                command: "HistoryResult".to_string(),
+                column,
+                series: series.into_iter()
+                    .map(|(key, points)| HistorySeries { key, points })
+                    .collect(),
+            };
+            send_watch_message(reply, r).await.map_err(|e| e.to_string())?;
+            false
+        },
+        ClientMessage::GetSnapshot { run_id } => {
+            let r = SetSnapshot {
+                command: "SetSnapshot".to_string(),
+                snapshot: colines.get_snapshot(run_id)?,
+            };
+            send_watch_message(reply, r).await.map_err(|e| e.to_string())?;
+            false
+        },
+        ClientMessage::SetReplay { paused, speed } => {
+            let replay_ref = replay.as_mut().ok_or("Not replaying a recording")?;
+            if let Some(paused) = paused {
+                replay_ref.set_paused(paused);
+            }
+            if let Some(speed) = speed {
+                replay_ref.set_speed(speed)?;
+            }
+            send_replay_state(writer, replay_ref).await;
+            false
+        },
+        ClientMessage::StepReplay => {
+            replay.as_mut().ok_or("Not replaying a recording")?.step();
+            false
+        },
+        ClientMessage::Export { format } => {
+            // The view is what was last sent, so it is exported as shown
+            let (_, labels, _) = colines.get_view_headers();
+            let r = ExportResult {
+                command: "ExportResult".to_string(),
+                filename: format!("vigilant-{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), format.extension()),
+                content: export::render(format, &cmdline, &labels, &colines.get_view_rows()),
+            };
+            send_watch_message(reply, r).await.map_err(|e| e.to_string())?;
+            false
+        },
+        ClientMessage::ExportHistory { columns, from, to, format } => {
+            let runs = colines.stored_runs(from, to)?;
+            let points = colines.history_points(&runs, &columns)?;
+            let r = ExportResult {
+                command: "ExportResult".to_string(),
+                filename: format!("vigilant-history-{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), format.extension()),
+                content: export::render_history(format, &points)?,
+            };
+            send_watch_message(reply, r).await.map_err(|e| e.to_string())?;
+            false
+        },
+        ClientMessage::DiffRuns { from, to } => {
+            let r = SetRunDiff {
+                command: "SetRunDiff".to_string(),
+                diff: colines.get_run_diff(from, to)?,
+            };
+            send_watch_message(reply, r).await.map_err(|e| e.to_string())?;
+            false
+        },
+    };
+    if resend {
+        // Resend the rows which moved, appeared or went away
+        send_table_update(writer, colines).await;
+    }
+    Ok(())
 }
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+/// Raises desktop notifications while the window is in the background. At
+/// most one is shown per gap so that a flapping alert does not flood the
+/// desktop; those held back in between are mentioned in the next one.
+pub struct Notifier {
+    app: AppHandle,
+    gap: Duration,
+    last: Option<Instant>,  // When the last notification was shown
+    held: Vec<String>,      // Titles of the notifications held back since
+}
+
+impl Notifier {
+    pub fn new(app: AppHandle, gap: Duration) -> Notifier {
+        Notifier { app, gap, last: None, held: Vec::new() }
+    }
+
+    pub fn notify(&mut self, title: &str, body: &str) {
+        // Nothing is missed while the window is in front
+        let focused = self.app.get_webview_window("main")
+            .and_then(|window| window.is_focused().ok())
+            .unwrap_or(false);
+        if focused {
+            return;
+        }
+
+        let now = Instant::now();
+        if self.last.is_some_and(|last| now.duration_since(last) < self.gap) {
+            self.held.push(title.to_string());
+            return;
+        }
+        self.last = Some(now);
+
+        let mut body = body.to_string();
+        if !self.held.is_empty() {
+            let mut earlier = self.held.iter().take(3).cloned().collect::<Vec<_>>().join(", ");
+            if self.held.len() > 3 {
+                earlier.push_str(&format!(" and {} more", self.held.len() - 3));
+            }
+            body.push_str(&format!("\nEarlier: {}", earlier));
+            self.held.clear();
+        }
+        if let Err(e) = self.app.notification().builder().title(title).body(body).show() {
+            eprintln!("Cannot show notification: {}", e);
+        }
+    }
+}
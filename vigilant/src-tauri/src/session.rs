@@ -0,0 +1,299 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Serialize, Deserialize};
+use crate::cmd::{ColumnSpec, SortSpec};
+use crate::history::Run;
+
+// Recordings are described in docs/session-format.md, keep it up to date
+// when changing anything written here.
+
+/// Name found in the header of every recording.
+pub const FORMAT: &str = "vigilant-session";
+
+/// Latest version of the recording format this build reads and writes.
+pub const VERSION: u32 = 1;
+
+/// First line of a recording, describing what was run.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionHeader {
+    pub format: String,
+    pub version: u32,
+    pub cmdline: String,
+    #[serde(default)]
+    pub interval: u64,   // Seconds between runs when recorded
+    #[serde(default)]
+    pub started: String, // RFC 3339 time the recording started
+    #[serde(default)]
+    pub config: SessionConfig,
+}
+
+/// How the table was set up when recording, applied again on replay.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub raw: bool,
+    pub filter: Option<String>,
+    pub columns: Option<Vec<ColumnSpec>>,
+    pub key: Option<String>,
+    pub rates: Vec<String>,
+    pub aggregates: Vec<String>,
+    pub group_by: Option<String>,
+    pub sort: Option<SortSpec>,
+}
+
+/// A run as written to a recording, with the headers parsed from it.
+#[derive(Serialize, Debug)]
+struct RecordedRun<'a> {
+    #[serde(flatten)]
+    run: &'a Run,
+    headers: Vec<String>,
+}
+
+/// Writes a recording as runs happen. Every line is a gzip member of its
+/// own, so the file is complete after each run: the program ends without
+/// anything left to finish, and a crash loses at most the run being written.
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+    last_id: u64,  // Id of the last run written
+}
+
+impl Recorder {
+    /// Creates the file, replacing any previous one, and writes the header.
+    pub fn create(path: &Path, cmdline: &str, interval: u64, config: SessionConfig) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+        let mut recorder = Recorder { file, last_id: 0 };
+        let header = SessionHeader {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            cmdline: cmdline.to_string(),
+            interval,
+            started: chrono::Utc::now().to_rfc3339(),
+            config,
+        };
+        recorder.write_line(&header)
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        Ok(recorder)
+    }
+
+    /// Writes a run. Ids only go up in a recording: a watch started over
+    /// without a run store numbers its runs from one again, those runs are
+    /// written after the last one instead.
+    pub fn record(&mut self, run: &Run) -> io::Result<()> {
+        let headers = run.lines.first()
+            .map(|line| line.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+        let id = run.id.max(self.last_id + 1);
+        let renumbered;
+        let run = if id == run.id {
+            run
+        } else {
+            renumbered = Run { id, ..run.clone() };
+            &renumbered
+        };
+        self.write_line(&RecordedRun { run, headers })?;
+        self.last_id = id;
+        Ok(())
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        let mut line = serde_json::to_string(value)?;
+        line.push('\n');
+        let mut encoder = GzEncoder::new(&mut self.file, Compression::default());
+        encoder.write_all(line.as_bytes())?;
+        encoder.finish()?.flush()
+    }
+}
+
+/// A recording read back from a file.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub header: SessionHeader,
+    pub runs: Vec<Run>,
+}
+
+/// Reads a recording: a header line then one run per line, all JSON, gzip
+/// compressed or not.
+pub fn read(path: &Path) -> Result<Session, String> {
+    let mut file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let mut magic = [0u8; 2];
+    let gzipped = file.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let reader: Box<dyn BufRead> = if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    let mut lines = reader.lines();
+    let first = lines.next()
+        .ok_or_else(|| format!("{} is empty", path.display()))?
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let header: SessionHeader = serde_json::from_str(&first)
+        .map_err(|e| format!("{} is not a recording: {}", path.display(), e))?;
+    if header.format != FORMAT {
+        return Err(format!("{} is not a recording", path.display()));
+    }
+    if header.version > VERSION {
+        return Err(format!("{} is a version {} recording, only version {} and older can be read",
+            path.display(), header.version, VERSION));
+    }
+
+    let mut runs = Vec::new();
+    for (n, line) in lines.enumerate() {
+        // A recording cut short by a crash may end in a partial gzip member
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Stopping at line {} of {}: {}", n + 2, path.display(), e);
+                break;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Run>(&line) {
+            Ok(run) => runs.push(run),
+            // A recording cut short still plays up to where it stops
+            Err(e) => {
+                eprintln!("Stopping at bad run on line {} of {}: {}", n + 2, path.display(), e);
+                break;
+            },
+        }
+    }
+    if runs.is_empty() {
+        return Err(format!("{} has no runs", path.display()));
+    }
+    Ok(Session { header, runs })
+}
+
+/// Plays the runs of a recording back, paced by the times they were taken.
+#[derive(Debug)]
+pub struct Replay {
+    runs: Vec<Run>,
+    position: usize,  // Next run to play
+    paused: bool,
+    speed: f64,       // 2.0 plays twice as fast as recorded
+    step: bool,       // Play the next run now, even when paused
+}
+
+/// State of a replay as shown to the UI.
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplayState {
+    pub position: usize,  // Runs played so far
+    pub count: usize,
+    pub paused: bool,
+    pub speed: f64,
+    pub timestamp: Option<f64>,  // When the run last played was recorded
+}
+
+impl Replay {
+    /// Starts a replay with the first run of the session already played.
+    pub fn new(session: &Session, speed: f64) -> Replay {
+        Replay {
+            runs: session.runs.clone(),
+            position: 1,
+            paused: false,
+            speed: if speed > 0.0 { speed } else { 1.0 },
+            step: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), String> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(format!("Bad replay speed {}", speed));
+        }
+        self.speed = speed;
+        Ok(())
+    }
+
+    /// Asks for the next run to be played right away.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    /// Whether a run is waiting to be played.
+    pub fn is_ready(&self) -> bool {
+        self.position < self.runs.len() && (!self.paused || self.step)
+    }
+
+    /// How long to wait after the previous run before playing the next one.
+    pub fn delay(&self) -> Duration {
+        if self.step || self.position == 0 || self.position >= self.runs.len() {
+            return Duration::ZERO;
+        }
+        let secs = self.runs[self.position].timestamp - self.runs[self.position - 1].timestamp;
+        Duration::from_secs_f64((secs / self.speed).max(0.0))
+    }
+
+    /// Returns the next run to play.
+    pub fn next_run(&mut self) -> Option<Run> {
+        let run = self.runs.get(self.position).cloned()?;
+        self.position += 1;
+        self.step = false;
+        Some(run)
+    }
+
+    pub fn state(&self) -> ReplayState {
+        ReplayState {
+            position: self.position,
+            count: self.runs.len(),
+            paused: self.paused,
+            speed: self.speed,
+            timestamp: self.position.checked_sub(1).and_then(|i| self.runs.get(i)).map(|run| run.timestamp),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(id: u64, value: &str) -> Run {
+        Run {
+            id,
+            timestamp: 1_700_000_000.0 + id as f64,
+            duration: 0.1,
+            status: Some(0),
+            lines: vec!["NAME VAL".to_string(), format!("a {}", value)],
+            stderr: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn recordings_are_complete_after_every_run() {
+        let path = std::env::temp_dir().join(format!("vigilant-session-{}.jsonl.gz", std::process::id()));
+        let mut recorder = Recorder::create(&path, "ps aux", 2, SessionConfig::default()).unwrap();
+        recorder.record(&run(1, "1")).unwrap();
+        recorder.record(&run(2, "2")).unwrap();
+        // A watch started over numbers its runs from one again
+        recorder.record(&run(1, "3")).unwrap();
+        // Left without finishing, as when the program exits
+        drop(recorder);
+
+        let mut text = String::new();
+        MultiGzDecoder::new(File::open(&path).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(text.lines().count(), 4);
+
+        let session = read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(session.header.cmdline, "ps aux");
+        assert_eq!(session.header.interval, 2);
+        let ids: Vec<u64> = session.runs.iter().map(|run| run.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(session.runs[2].lines[1], "a 3");
+    }
+}
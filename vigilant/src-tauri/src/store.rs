@@ -0,0 +1,452 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use chrono::Utc;
+use crate::history::Run;
+use crate::value::{self, CellKind};
+
+/// Runs appended to a segment before it is compressed and a new one started.
+const SEGMENT_RUNS: usize = 500;
+
+/// Uncompressed bytes appended to a segment before starting a new one.
+const SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+
+/// How much run history is kept on disk. Whole segments are removed, oldest
+/// first, so the limits are honoured to within one segment.
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    pub max_runs: Option<usize>,
+    pub max_age: Option<f64>,   // Seconds
+    pub max_bytes: Option<u64>, // Size on disk
+}
+
+impl Retention {
+    /// Builds the limits from their text form, e.g. an age of `30d` and a
+    /// size of `256M`.
+    pub fn parse(runs: Option<usize>, age: Option<&str>, size: Option<&str>) -> Result<Retention, String> {
+        let max_age = match age {
+            Some(text) => match value::parse_cell(text) {
+                (CellKind::Duration, Some(secs)) | (CellKind::Integer, Some(secs)) => Some(secs),
+                _ => return Err(format!("Bad age {}, expected e.g. 12h or 30d", text)),
+            },
+            None => None,
+        };
+        let max_bytes = match size {
+            Some(text) => match value::parse_cell(text) {
+                (CellKind::Size, Some(bytes)) | (CellKind::Integer, Some(bytes)) => Some(bytes as u64),
+                _ => return Err(format!("Bad size {}, expected e.g. 512M or 2G", text)),
+            },
+            None => None,
+        };
+        Ok(Retention { max_runs: runs, max_age, max_bytes })
+    }
+}
+
+/// A file of runs, either the open JSON lines segment or a compressed one.
+#[derive(Debug)]
+struct Segment {
+    seq: u64,
+    path: PathBuf,
+    runs: usize,
+    bytes: u64,
+    first_id: u64,
+    last_id: u64,
+    first_time: f64,
+    last_time: f64,
+}
+
+impl Segment {
+    fn is_open(&self) -> bool {
+        self.path.extension().is_some_and(|ext| ext == "jsonl")
+    }
+
+    fn add(&mut self, run: &Run, bytes: u64) {
+        if self.runs == 0 {
+            self.first_id = run.id;
+            self.first_time = run.timestamp;
+        }
+        self.runs += 1;
+        self.bytes += bytes;
+        self.last_id = run.id;
+        self.last_time = run.timestamp;
+    }
+}
+
+/// Append-only log of the runs of one command. Runs are appended as JSON
+/// lines to the open segment, which is gzip compressed once full.
+#[derive(Debug)]
+pub struct RunStore {
+    dir: PathBuf,
+    retention: Retention,
+    segments: Vec<Segment>, // Oldest first
+    file: Option<File>,     // Open segment, always the last one
+    _lock: File,            // Held while the store is open
+}
+
+/// Default directory for the stored runs, under the XDG data directory.
+pub fn default_dir() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("share"),
+    };
+    Some(dir.join("vigilant").join("runs"))
+}
+
+/// Directory keeping the runs of a command line, below the given one.
+pub fn command_dir(base: &Path, cmdline: &str) -> PathBuf {
+    let name: String = cmdline.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .take(100)
+        .collect();
+    base.join(name.trim_start_matches('.'))
+}
+
+/// Creates a directory and its missing parents, readable by the user only
+/// since runs hold whatever the command printed.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        // Also when it was created by an earlier version with the default mode
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+    }
+    #[cfg(not(unix))]
+    fs::create_dir_all(dir)
+}
+
+/// Options creating files readable by the user only.
+fn private_file() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+fn read_segment(path: &Path) -> io::Result<Vec<Run>> {
+    let file = File::open(path)?;
+    let reader: Box<dyn BufRead> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(BufReader::new(GzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    let mut runs = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        match serde_json::from_str::<Run>(&line) {
+            Ok(run) => runs.push(run),
+            // A line cut short by a crash, the rest of the segment is fine
+            Err(e) => eprintln!("Skipping bad run in {}: {}", path.display(), e),
+        }
+    }
+    Ok(runs)
+}
+
+/// Cuts off the line a crash left unfinished at the end of the open segment,
+/// so that the next run starts a line of its own. Returns the bytes kept.
+fn cut_partial_line(path: &Path) -> io::Result<u64> {
+    let data = fs::read(path)?;
+    let keep = match data.last() {
+        Some(&last) if last != b'\n' => data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1),
+        _ => data.len(),
+    };
+    if keep < data.len() {
+        eprintln!("Cutting off a run left unfinished in {}", path.display());
+        OpenOptions::new().write(true).open(path)?.set_len(keep as u64)?;
+    }
+    Ok(keep as u64)
+}
+
+impl RunStore {
+    /// Opens the store kept in a directory, creating it when needed. Only
+    /// one store may be open on a directory at a time, in this process or
+    /// any other, as both would append runs under the same ids.
+    pub fn open(dir: PathBuf, retention: Retention) -> io::Result<RunStore> {
+        create_private_dir(&dir)?;
+        let lock = private_file().create(true).truncate(false).write(true).open(dir.join("lock"))?;
+        match lock.try_lock() {
+            Ok(()) => {},
+            Err(fs::TryLockError::WouldBlock) => return Err(io::Error::new(io::ErrorKind::WouldBlock,
+                format!("{} is in use, by another watch of the same command or another vigilant", dir.display()))),
+            Err(fs::TryLockError::Error(e)) => return Err(e),
+        }
+
+        let mut found: BTreeMap<u64, PathBuf> = BTreeMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let seq = name.strip_prefix("runs-")
+                .and_then(|n| n.strip_suffix(".jsonl.gz").or_else(|| n.strip_suffix(".jsonl")))
+                .and_then(|n| n.parse::<u64>().ok());
+            let seq = match seq {
+                Some(seq) => seq,
+                None => continue,
+            };
+            // A segment compressed just before a crash may still have its original
+            match found.get(&seq) {
+                Some(other) if name.ends_with(".gz") => {
+                    fs::remove_file(other)?;
+                    found.insert(seq, path);
+                },
+                Some(_) => fs::remove_file(&path)?,
+                None => {
+                    found.insert(seq, path);
+                },
+            }
+        }
+
+        let mut segments = Vec::new();
+        for (seq, path) in found {
+            let mut segment = Segment { seq, path, runs: 0, bytes: 0, first_id: 0, last_id: 0, first_time: 0.0, last_time: 0.0 };
+            for run in read_segment(&segment.path)? {
+                segment.add(&run, 0);
+            }
+            segment.bytes = fs::metadata(&segment.path)?.len();
+            segments.push(segment);
+        }
+
+        let mut store = RunStore { dir, retention, segments, file: None, _lock: lock };
+        // Only the newest segment is appended to, older ones left open are closed
+        let last = store.segments.len().saturating_sub(1);
+        for i in 0..last {
+            if store.segments[i].is_open() {
+                store.compress(i)?;
+            }
+        }
+        if let Some(segment) = store.segments.last_mut() {
+            if segment.is_open() {
+                segment.bytes = cut_partial_line(&segment.path)?;
+                store.file = Some(OpenOptions::new().append(true).open(&segment.path)?);
+            }
+        }
+        store.enforce_retention()?;
+        Ok(store)
+    }
+
+    /// Id of the oldest stored run.
+    pub fn first_id(&self) -> Option<u64> {
+        self.segments.iter().find(|s| s.runs > 0).map(|s| s.first_id)
+    }
+
+    /// Id of the newest stored run.
+    pub fn last_id(&self) -> Option<u64> {
+        self.segments.iter().rev().find(|s| s.runs > 0).map(|s| s.last_id)
+    }
+
+    /// Appends a run, starting a new segment when the open one is full.
+    pub fn append(&mut self, run: &Run) -> io::Result<()> {
+        let full = self.segments.last()
+            .is_none_or(|s| !s.is_open() || s.runs >= SEGMENT_RUNS || s.bytes >= SEGMENT_BYTES);
+        if full {
+            self.start_segment()?;
+        }
+
+        let mut line = serde_json::to_string(run)?;
+        line.push('\n');
+        let file = self.file.as_mut().expect("no open segment");
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        if let Some(segment) = self.segments.last_mut() {
+            segment.add(run, line.len() as u64);
+        }
+        self.enforce_retention()
+    }
+
+    /// Returns the run with the given id, if it is still kept.
+    pub fn get(&self, id: u64) -> io::Result<Option<Run>> {
+        match self.segments.iter().find(|s| s.runs > 0 && s.first_id <= id && id <= s.last_id) {
+            Some(segment) => Ok(read_segment(&segment.path)?.into_iter().find(|run| run.id == id)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the runs taken between two times, both optional and inclusive.
+    pub fn range(&self, from: Option<f64>, to: Option<f64>) -> io::Result<Vec<Run>> {
+        let mut runs = Vec::new();
+        for segment in &self.segments {
+            if segment.runs == 0
+                || from.is_some_and(|t| segment.last_time < t)
+                || to.is_some_and(|t| segment.first_time > t) {
+                continue;
+            }
+            runs.extend(read_segment(&segment.path)?.into_iter()
+                .filter(|run| from.is_none_or(|t| run.timestamp >= t))
+                .filter(|run| to.is_none_or(|t| run.timestamp <= t)));
+        }
+        Ok(runs)
+    }
+
+    /// Returns the newest runs, at most count of them, oldest first.
+    pub fn tail(&self, count: usize) -> io::Result<Vec<Run>> {
+        let mut runs: Vec<Run> = Vec::new();
+        for segment in self.segments.iter().rev() {
+            if runs.len() >= count {
+                break;
+            }
+            let mut older = read_segment(&segment.path)?;
+            older.append(&mut runs);
+            runs = older;
+        }
+        let skip = runs.len().saturating_sub(count);
+        Ok(runs.split_off(skip))
+    }
+
+    fn start_segment(&mut self) -> io::Result<()> {
+        if let Some(i) = self.segments.len().checked_sub(1) {
+            if self.segments[i].is_open() {
+                self.file = None;
+                self.compress(i)?;
+            }
+        }
+        let seq = self.segments.last().map_or(1, |s| s.seq + 1);
+        let path = self.dir.join(format!("runs-{:08}.jsonl", seq));
+        self.file = Some(private_file().create(true).append(true).open(&path)?);
+        self.segments.push(Segment { seq, path, runs: 0, bytes: 0, first_id: 0, last_id: 0, first_time: 0.0, last_time: 0.0 });
+        Ok(())
+    }
+
+    /// Compresses a full segment, replacing the JSON lines file.
+    fn compress(&mut self, i: usize) -> io::Result<()> {
+        let segment = &mut self.segments[i];
+        let path = segment.path.with_extension("jsonl.gz");
+        let tmp = segment.path.with_extension("jsonl.gz.tmp");
+        let mut encoder = GzEncoder::new(private_file().create(true).write(true).truncate(true).open(&tmp)?, Compression::default());
+        io::copy(&mut File::open(&segment.path)?, &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        fs::remove_file(&segment.path)?;
+        segment.bytes = fs::metadata(&path)?.len();
+        segment.path = path;
+        Ok(())
+    }
+
+    /// Removes the oldest segments while any retention limit is exceeded.
+    /// The segment being appended to is always kept.
+    fn enforce_retention(&mut self) -> io::Result<()> {
+        let now = Utc::now().timestamp_millis() as f64 / 1000.0;
+        while self.segments.len() > 1 {
+            let runs: usize = self.segments.iter().map(|s| s.runs).sum();
+            let bytes: u64 = self.segments.iter().map(|s| s.bytes).sum();
+            let oldest = &self.segments[0];
+            let over = self.retention.max_runs.is_some_and(|max| runs - oldest.runs >= max)
+                || self.retention.max_bytes.is_some_and(|max| bytes > max)
+                || self.retention.max_age.is_some_and(|max| oldest.last_time < now - max);
+            if !over {
+                break;
+            }
+            fs::remove_file(&oldest.path)?;
+            self.segments.remove(0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::RunHistory;
+
+    fn run(id: u64) -> Run {
+        Run {
+            id,
+            timestamp: Utc::now().timestamp() as f64,
+            duration: 0.1,
+            status: Some(0),
+            lines: vec!["NAME VAL".to_string(), format!("a {}", id)],
+            stderr: Vec::new(),
+        }
+    }
+
+    /// An empty directory of its own for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vigilant-store-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn ids(runs: &[Run]) -> Vec<u64> {
+        runs.iter().map(|run| run.id).collect()
+    }
+
+    #[test]
+    fn retention_drops_whole_segments() {
+        let dir = test_dir("retention");
+        let retention = Retention { max_runs: Some(SEGMENT_RUNS), ..Retention::default() };
+        let mut store = RunStore::open(dir.clone(), retention).unwrap();
+        for id in 1..=2 * SEGMENT_RUNS as u64 + 1 {
+            store.append(&run(id)).unwrap();
+        }
+        // The oldest segment went at once, the next one is kept whole
+        assert_eq!(store.first_id(), Some(SEGMENT_RUNS as u64 + 1));
+        assert_eq!(store.last_id(), Some(2 * SEGMENT_RUNS as u64 + 1));
+        assert_eq!(store.tail(usize::MAX).unwrap().len(), SEGMENT_RUNS + 1);
+        assert!(!dir.join("runs-00000001.jsonl.gz").exists());
+        assert!(dir.join("runs-00000002.jsonl.gz").exists());
+        assert!(dir.join("runs-00000003.jsonl").exists());
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_one_store_is_open_on_a_directory() {
+        let dir = test_dir("lock");
+        let store = RunStore::open(dir.clone(), Retention::default()).unwrap();
+        let e = RunStore::open(dir.clone(), Retention::default()).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+        drop(store);
+        // Let go of once closed
+        RunStore::open(dir.clone(), Retention::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reopened_store_numbers_runs_after_the_last() {
+        let dir = test_dir("reopen");
+        let mut store = RunStore::open(dir.clone(), Retention::default()).unwrap();
+        let mut history = RunHistory::new(10);
+        for _ in 0..3 {
+            let id = history.push(run(0));
+            store.append(history.get(id).unwrap()).unwrap();
+        }
+        drop(store);
+
+        let mut store = RunStore::open(dir.clone(), Retention::default()).unwrap();
+        let mut history = RunHistory::new(10);
+        history.resume(store.tail(10).unwrap(), store.last_id());
+        let id = history.push(run(0));
+        assert_eq!(id, 4);
+        store.append(history.get(id).unwrap()).unwrap();
+        assert_eq!(ids(&store.tail(10).unwrap()), vec![1, 2, 3, 4]);
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_last_segment_is_recovered() {
+        let dir = test_dir("truncated");
+        let mut store = RunStore::open(dir.clone(), Retention::default()).unwrap();
+        store.append(&run(1)).unwrap();
+        store.append(&run(2)).unwrap();
+        drop(store);
+        // A crash in the middle of writing the third run
+        let path = dir.join("runs-00000001.jsonl");
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"id":3,"timestamp":17"#).unwrap();
+        drop(file);
+
+        let mut store = RunStore::open(dir.clone(), Retention::default()).unwrap();
+        assert_eq!(store.last_id(), Some(2));
+        store.append(&run(3)).unwrap();
+        assert_eq!(ids(&store.tail(10).unwrap()), vec![1, 2, 3]);
+        assert_eq!(store.get(3).unwrap().unwrap().lines[1], "a 3");
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Serialize, Deserialize};
+
+/// Kind of values found in a column of the command output.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CellKind {
+    Text,
+    Integer,
+    Float,
+    Percent,
+    Size,      // Normalized to bytes
+    Duration,  // Normalized to seconds
+    Timestamp, // Normalized to seconds since the epoch
+}
+
+/// Parses a single cell and returns its kind with the normalized numeric value.
+pub fn parse_cell(text: &str) -> (CellKind, Option<f64>) {
+    let t = text.trim();
+    if t.is_empty() {
+        return (CellKind::Text, None);
+    }
+    if let Some(v) = parse_number(t) {
+        if t.contains(['.', 'e', 'E']) {
+            return (CellKind::Float, Some(v));
+        }
+        return (CellKind::Integer, Some(v));
+    }
+    if let Some(num) = t.strip_suffix('%') {
+        if let Some(v) = parse_number(num) {
+            return (CellKind::Percent, Some(v));
+        }
+    }
+    if let Some(v) = parse_size(t) {
+        return (CellKind::Size, Some(v));
+    }
+    if let Some(v) = parse_duration(t) {
+        return (CellKind::Duration, Some(v));
+    }
+    if let Some(v) = parse_timestamp(t) {
+        return (CellKind::Timestamp, Some(v));
+    }
+    (CellKind::Text, None)
+}
+
+/// Infers the kind of a column from all of its cells. Empty cells and the
+/// usual "-" placeholder are ignored; plain numbers mixed with floats or
+/// sizes widen to those kinds, anything else mixed makes the column text.
+pub fn infer_kind<'a, I>(cells: I) -> CellKind
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut kind: Option<CellKind> = None;
+    for cell in cells {
+        let cell = cell.trim();
+        if cell.is_empty() || cell == "-" {
+            continue;
+        }
+        let (k, _) = parse_cell(cell);
+        kind = match (kind, k) {
+            (None, k) => Some(k),
+            (Some(a), b) if a == b => Some(a),
+            (Some(CellKind::Integer), CellKind::Float) | (Some(CellKind::Float), CellKind::Integer) => Some(CellKind::Float),
+            (Some(CellKind::Integer), CellKind::Size) | (Some(CellKind::Size), CellKind::Integer) => Some(CellKind::Size),
+            _ => return CellKind::Text,
+        };
+    }
+    kind.unwrap_or(CellKind::Text)
+}
+
+/// Returns the numeric value of a cell in a column of the given kind, None
+/// for text columns or cells which do not fit their column.
+pub fn number(text: &str, kind: CellKind) -> Option<f64> {
+    if kind == CellKind::Text {
+        return None;
+    }
+    match parse_cell(text) {
+        (_, None) => None,
+        (k, value) if k == kind => value,
+        (CellKind::Integer, value) if kind == CellKind::Float || kind == CellKind::Size => value,
+        _ => None,
+    }
+}
+
+/// Returns the numeric value of every cell, using the inferred column kinds.
+pub fn numbers(values: &[String], kinds: &[CellKind]) -> Vec<Option<f64>> {
+    values.iter().enumerate()
+        .map(|(i, v)| number(v, kinds.get(i).copied().unwrap_or(CellKind::Text)))
+        .collect()
+}
+
+fn parse_number(t: &str) -> Option<f64> {
+    // Refuse "inf", "nan" and friends which f64::from_str happily accepts
+    if !t.chars().any(|c| c.is_ascii_digit())
+        || !t.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')) {
+        return None;
+    }
+    t.parse::<f64>().ok()
+}
+
+/// Human readable sizes such as `512K`, `1.5G`, `10MiB` or `4.0kB`.
+fn parse_size(t: &str) -> Option<f64> {
+    let split = t.find(|c: char| c.is_ascii_alphabetic())?;
+    let (num, unit) = t.split_at(split);
+    let value = parse_number(num)?;
+    let power = match unit {
+        "B" => 0,
+        "K" | "k" | "KB" | "kB" | "KiB" => 1,
+        "M" | "MB" | "MiB" => 2,
+        "G" | "GB" | "GiB" => 3,
+        "T" | "TB" | "TiB" => 4,
+        "P" | "PB" | "PiB" => 5,
+        _ => return None,
+    };
+    Some(value * 1024f64.powi(power))
+}
+
+/// Durations, either clock style with hours, minutes and seconds
+/// (`1:23:45`, `1-02:03:04`, `01:02:03.5`) or with units (`3d`, `2h30m`,
+/// `45s`, `250ms`). Two part clocks such as `10:42` are left alone, they are
+/// as often a time of day, like the START column of ps, as a duration.
+fn parse_duration(t: &str) -> Option<f64> {
+    if t.contains(':') {
+        let (days, clock) = match t.split_once('-') {
+            Some((d, c)) if !d.is_empty() && d.chars().all(|c| c.is_ascii_digit()) => (d.parse::<u64>().ok()? as f64, c),
+            Some(_) => return None,
+            None => (0.0, t),
+        };
+        let parts: Vec<&str> = clock.split(':').collect();
+        let [hours, minutes, seconds] = parts[..] else {
+            return None;
+        };
+        if hours.is_empty() || !hours.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+        let two_digits = |p: &str| p.len() == 2 && p.chars().all(|c| c.is_ascii_digit()) && p < "60";
+        if !two_digits(minutes) || !two_digits(whole) || fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let secs = hours.parse::<f64>().ok()? * 3600.0 + minutes.parse::<f64>().ok()? * 60.0 + seconds.parse::<f64>().ok()?;
+        return Some(days * 86400.0 + secs);
+    }
+
+    let mut secs = 0.0;
+    let mut rest = t;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let value = parse_number(&rest[..split])?;
+        let unit_len = rest[split..].find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len() - split);
+        let unit = &rest[split..split + unit_len];
+        let scale = match unit {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" | "min" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            "w" => 604800.0,
+            _ => return None,
+        };
+        secs += value * scale;
+        rest = &rest[split + unit_len..];
+    }
+    Some(secs)
+}
+
+fn parse_timestamp(t: &str) -> Option<f64> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(t) {
+        return Some(dt.timestamp_millis() as f64 / 1000.0);
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%S") {
+        return Some(dt.and_utc().timestamp_millis() as f64 / 1000.0);
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(t, "%Y-%m-%d") {
+        return Some(d.and_hms_opt(0, 0, 0)?.and_utc().timestamp() as f64);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_numbers() {
+        assert_eq!(parse_cell("42"), (CellKind::Integer, Some(42.0)));
+        assert_eq!(parse_cell("-1.5"), (CellKind::Float, Some(-1.5)));
+        assert_eq!(parse_cell("1e3"), (CellKind::Float, Some(1000.0)));
+        assert_eq!(parse_cell("12.5%"), (CellKind::Percent, Some(12.5)));
+        assert_eq!(parse_cell("inf"), (CellKind::Text, None));
+        assert_eq!(parse_cell("nan"), (CellKind::Text, None));
+        assert_eq!(parse_cell(""), (CellKind::Text, None));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_cell("512K"), (CellKind::Size, Some(512.0 * 1024.0)));
+        assert_eq!(parse_cell("1.5G"), (CellKind::Size, Some(1.5 * 1024.0 * 1024.0 * 1024.0)));
+        assert_eq!(parse_cell("10MiB"), (CellKind::Size, Some(10.0 * 1024.0 * 1024.0)));
+        assert_eq!(parse_cell("3X").0, CellKind::Text);
+    }
+
+    #[test]
+    fn clock_durations() {
+        assert_eq!(parse_cell("1:23:45"), (CellKind::Duration, Some(5025.0)));
+        assert_eq!(parse_cell("01:02:03.5"), (CellKind::Duration, Some(3723.5)));
+        assert_eq!(parse_cell("1-02:03:04"), (CellKind::Duration, Some(86400.0 + 7384.0)));
+        assert_eq!(parse_cell("123:00:00"), (CellKind::Duration, Some(123.0 * 3600.0)));
+    }
+
+    #[test]
+    fn times_of_day_are_not_durations() {
+        assert_eq!(parse_cell("10:42").0, CellKind::Text);
+        assert_eq!(parse_cell("0:00").0, CellKind::Text);
+        assert_eq!(parse_cell("12:34.56").0, CellKind::Text);
+        assert_eq!(parse_cell("1:2:3").0, CellKind::Text);
+        assert_eq!(parse_cell("1:75:00").0, CellKind::Text);
+        assert_eq!(parse_cell("a-01:02:03").0, CellKind::Text);
+        assert_eq!(parse_cell("1:02:03:04").0, CellKind::Text);
+    }
+
+    #[test]
+    fn unit_durations() {
+        assert_eq!(parse_cell("45s"), (CellKind::Duration, Some(45.0)));
+        assert_eq!(parse_cell("2h30m"), (CellKind::Duration, Some(9000.0)));
+        assert_eq!(parse_cell("250ms"), (CellKind::Duration, Some(0.25)));
+        assert_eq!(parse_cell("3d"), (CellKind::Duration, Some(259200.0)));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_cell("1970-01-02"), (CellKind::Timestamp, Some(86400.0)));
+        assert_eq!(parse_cell("1970-01-01T00:01:00"), (CellKind::Timestamp, Some(60.0)));
+        assert_eq!(parse_cell("1970-01-01T00:00:01Z"), (CellKind::Timestamp, Some(1.0)));
+    }
+
+    #[test]
+    fn inference() {
+        assert_eq!(infer_kind(["1", "2", "3"]), CellKind::Integer);
+        assert_eq!(infer_kind(["1", "2.5"]), CellKind::Float);
+        assert_eq!(infer_kind(["1", "4K"]), CellKind::Size);
+        assert_eq!(infer_kind(["1", "-", "", "3"]), CellKind::Integer);
+        assert_eq!(infer_kind(["1", "abc"]), CellKind::Text);
+        assert_eq!(infer_kind(["5%", "1"]), CellKind::Text);
+        assert_eq!(infer_kind(["09:15", "10:42"]), CellKind::Text);
+        assert_eq!(infer_kind(["-", ""]), CellKind::Text);
+    }
+
+    #[test]
+    fn numbers_fit_their_column() {
+        assert_eq!(number("3", CellKind::Float), Some(3.0));
+        assert_eq!(number("3", CellKind::Size), Some(3.0));
+        assert_eq!(number("4K", CellKind::Integer), None);
+        assert_eq!(number("3", CellKind::Text), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use serde::{Serialize, Deserialize};
+use tokio::sync::mpsc;
+
+// Deliveries are described in docs/webhooks.md, keep it up to date.
+
+/// Attempts at delivering an event before giving up on it.
+const ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry, doubled for every following one.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long a receiver may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events waiting for delivery to an endpoint, more are dropped.
+const QUEUE: usize = 100;
+
+/// Numbers the deliveries, so that receivers can tell retries apart.
+static DELIVERIES: AtomicU64 = AtomicU64::new(1);
+
+/// An HTTP endpoint alert and change events are posted to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub secret: Option<String>,  // Signs the deliveries when set
+    #[serde(default)]
+    pub events: Vec<String>,     // change or alert, every event when empty
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<reqwest::Url, String> {
+        let url = reqwest::Url::parse(&self.url).map_err(|e| format!("Bad webhook URL {}: {}", self.url, e))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(format!("Webhook URL {} is not http or https", self.url));
+        }
+        if let Some(event) = self.events.iter().find(|e| *e != "change" && *e != "alert") {
+            return Err(format!("Unknown webhook event {}, use change or alert", event));
+        }
+        Ok(url)
+    }
+}
+
+#[derive(Debug)]
+struct Delivery {
+    id: String,
+    event: &'static str,
+    body: String,
+}
+
+/// Posts events to an endpoint from a task of its own, one at a time and in
+/// order, retrying with backoff while the receiver is unreachable or fails.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    events: Vec<String>,
+    sender: mpsc::Sender<Delivery>,
+}
+
+impl Webhook {
+    /// Starts the delivery task, which needs a Tokio runtime.
+    pub fn start(config: WebhookConfig) -> Result<Webhook, String> {
+        let url = config.validate()?;
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("vigilant/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| format!("Cannot set up webhook {}: {}", config.url, e))?;
+        let (sender, mut receiver) = mpsc::channel(QUEUE);
+        let secret = config.secret.clone();
+        tokio::spawn(async move {
+            while let Some(delivery) = receiver.recv().await {
+                deliver(&client, &url, secret.as_deref(), &delivery).await;
+            }
+        });
+        Ok(Webhook { url: config.url, events: config.events, sender })
+    }
+
+    /// Queues an event, unless the endpoint does not want it.
+    pub fn post(&self, event: &'static str, body: &str) {
+        if !self.events.is_empty() && !self.events.iter().any(|e| e == event) {
+            return;
+        }
+        let delivery = Delivery {
+            id: format!("{}-{}", std::process::id(), DELIVERIES.fetch_add(1, Ordering::Relaxed)),
+            event,
+            body: body.to_string(),
+        };
+        if let Err(e) = self.sender.try_send(delivery) {
+            eprintln!("Dropping {} event for {}: {}", event, self.url, e);
+        }
+    }
+}
+
+/// Signature of a delivery: the hex HMAC-SHA256 of `timestamp.body`, so
+/// that an old delivery cannot be sent again as a new one.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn deliver(client: &reqwest::Client, url: &reqwest::Url, secret: Option<&str>, delivery: &Delivery) {
+    let mut backoff = FIRST_BACKOFF;
+    for attempt in 1..=ATTEMPTS {
+        let timestamp = chrono::Utc::now().timestamp();
+        let mut request = client.post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Vigilant-Event", delivery.event)
+            .header("X-Vigilant-Delivery", &delivery.id)
+            .header("X-Vigilant-Timestamp", timestamp.to_string())
+            .body(delivery.body.clone());
+        if let Some(secret) = secret {
+            request = request.header("X-Vigilant-Signature", format!("sha256={}", sign(secret, timestamp, &delivery.body)));
+        }
+
+        let mut wait = backoff;
+        let retry = match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => {
+                let status = response.status();
+                eprintln!("Webhook {} answered {} to delivery {}", url, status, delivery.id);
+                // A receiver asking to slow down says for how long
+                if let Some(secs) = response.headers().get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok()) {
+                    wait = Duration::from_secs(secs).min(MAX_BACKOFF);
+                }
+                // Other client errors would fail the same way again
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT
+            },
+            Err(e) => {
+                eprintln!("Webhook {} failed for delivery {}: {}", url, delivery.id, e);
+                true
+            },
+        };
+        if !retry || attempt == ATTEMPTS {
+            eprintln!("Giving up on delivery {} to {} after {} attempts", delivery.id, url, attempt);
+            return;
+        }
+        tokio::time::sleep(wait).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A request received by the test receiver, header names in lower case.
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: String,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> &str {
+            self.headers.iter().find(|(n, _)| n == name).map_or("", |(_, v)| v.as_str())
+        }
+    }
+
+    /// Starts a receiver answering the first requests with the given
+    /// statuses and 200 afterwards, and returns its URL and what it got.
+    async fn receiver(statuses: Vec<&'static str>) -> (reqwest::Url, Arc<Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = reqwest::Url::parse(&format!("http://{}/hook", listener.local_addr().unwrap())).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut data = Vec::new();
+                let mut buf = [0u8; 4096];
+                let (head, body) = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    data.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&data).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head.lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if body.len() >= length || n == 0 {
+                            break (head.to_string(), body.to_string());
+                        }
+                    }
+                };
+                let headers = head.lines().skip(1)
+                    .filter_map(|l| l.split_once(':'))
+                    .map(|(n, v)| (n.trim().to_ascii_lowercase(), v.trim().to_string()))
+                    .collect();
+                let count = {
+                    let mut log = log.lock().unwrap();
+                    log.push(Received { headers, body });
+                    log.len()
+                };
+                let status = statuses.get(count - 1).copied().unwrap_or("200 OK");
+                let response = format!("HTTP/1.1 {}\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn delivery(body: &str) -> Delivery {
+        Delivery { id: "1-1".to_string(), event: "alert", body: body.to_string() }
+    }
+
+    #[test]
+    fn signature() {
+        assert_eq!(sign("s3cret", 1700000000, "{\"a\":1}"),
+                   "1698a50bc74d1ff1db85c4e0a5297c2ad9fdba245d5737cdb789e4cc6e098940");
+    }
+
+    #[tokio::test]
+    async fn retries_until_delivered() {
+        let (url, received) = receiver(vec!["503 Service Unavailable", "500 Internal Server Error"]).await;
+        deliver(&reqwest::Client::new(), &url, Some("s3cret"), &delivery("{\"a\":1}")).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        for request in received.iter() {
+            assert_eq!(request.body, "{\"a\":1}");
+            assert_eq!(request.header("x-vigilant-event"), "alert");
+            assert_eq!(request.header("x-vigilant-delivery"), "1-1");
+            let timestamp: i64 = request.header("x-vigilant-timestamp").parse().unwrap();
+            assert_eq!(request.header("x-vigilant-signature"), format!("sha256={}", sign("s3cret", timestamp, "{\"a\":1}")));
+        }
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let (url, received) = receiver(vec!["503 Service Unavailable"; ATTEMPTS as usize + 1]).await;
+        deliver(&reqwest::Client::new(), &url, None, &delivery("{}")).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), ATTEMPTS as usize);
+        assert_eq!(received[0].header("x-vigilant-signature"), "");
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (url, received) = receiver(vec!["400 Bad Request"]).await;
+        deliver(&reqwest::Client::new(), &url, Some("s3cret"), &delivery("{}")).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+}
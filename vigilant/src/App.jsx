@@ -1,16 +1,23 @@
-import React, { useState, useEffect } from 'react';
-import VigilantTable from './components/TableView';
-import TitleBanner from './components/TitleBanner';
+import React, { useState, useEffect, useRef } from 'react';
+import WatchView from './components/WatchView';
+import LayoutBar from './components/LayoutBar';
+import WatchEditor from './components/WatchEditor';
 
 function App() {
     const [message, setMessage] = useState('');
     const [ws, setWs] = useState(null);
-    const [showData, setShowData] = useState([]);
-    const [headers, setHeaders] = useState([]);
-    const [leftTitle, setLeftTitle] = useState('Every 10.0 secs');
-    const [centerTitle, setCenterTitle] = useState('Main Title');
+    const [watches, setWatches] = useState([]); // Watched commands, one tab each
+    const [activeWatch, setActiveWatch] = useState(null);
+    const [layout, setLayout] = useState({ mode: 'tabs', columns: 2, tiles: [] }); // Dashboard grid kept by the server
+    const [editLayout, setEditLayout] = useState(false);
+    const [editing, setEditing] = useState(null); // Watch being changed, 'new' for one being added
+    const [alerting, setAlerting] = useState({}); // Number of firing alerts by watch id
+    const [serverError, setServerError] = useState(null); // Errors not about any watch
     const [rightTitle, setRightTitle] = useState(getCurrentTime());
     const [isConnected, setIsConnected] = useState(false);  // To track WebSocket connection status
+    const handlers = useRef({}); // Message handler of each watch view, by watch id
+    const pending = useRef({});  // Messages received before the view of their watch was shown
+    const changeSent = useRef(false); // Whether a watch change waits for the server to take it
 
     // Function to get the current time in the watch(1) format
     function getCurrentTime() {
@@ -35,6 +42,25 @@ function App() {
         return () => clearInterval(intervalId);
     }, []);
 
+    // Hands a message about a watch to its view, or keeps it until the view is shown
+    const dispatch = (jsonMessage) => {
+        const handler = handlers.current[jsonMessage.watch];
+        if (handler) {
+            handler(jsonMessage);
+        } else {
+            pending.current[jsonMessage.watch] = [...(pending.current[jsonMessage.watch] || []), jsonMessage];
+        }
+    };
+
+    const subscribe = (id) => (handler) => {
+        handlers.current[id] = handler;
+        (pending.current[id] || []).forEach(handler);
+        delete pending.current[id];
+        return () => {
+            delete handlers.current[id];
+        };
+    };
+
     const createWebSocket = () => {
         const websocket = new WebSocket('ws://127.0.0.1:8080');
 
@@ -48,28 +74,26 @@ function App() {
 
             try {
                 const jsonMessage = JSON.parse(event.data);
-                if (jsonMessage.command === 'SetTitle') {
-                    if (jsonMessage.left) setLeftTitle(jsonMessage.left);
-                    if (jsonMessage.right) setRightTitle(jsonMessage.right);
-                    if (jsonMessage.center) setCenterTitle(jsonMessage.center);
-                    console.log("Done setting Title ", jsonMessage);
-                } else if (jsonMessage.command === 'SetHeaders') {
-                    console.log("Setting headers");
-                    setHeaders(jsonMessage.headers); // Update headers
-                } else if (jsonMessage.command === 'SetDataRow') {
-                    const { index, values } = jsonMessage;
-                    console.log("Setting data");
-                    setShowData((prevData) => {
-                        const updatedData = [...prevData];
-                        if (updatedData[index]) {
-                          // Update the existing entry using the values array
-                          updatedData[index] = { ...updatedData[index], values };
-                        } else {
-                          // Add a new entry with the values array
-                          updatedData.push({ values });
-                        }
-                        return updatedData;
-                      });
+                if (jsonMessage.watch !== undefined) {
+                    dispatch(jsonMessage);
+                } else if (jsonMessage.command === 'SetWatches') {
+                    // The change was taken, the editor is done with
+                    if (changeSent.current) {
+                        changeSent.current = false;
+                        setEditing(null);
+                    }
+                    setWatches(jsonMessage.watches);
+                    setActiveWatch((prev) => (jsonMessage.watches.some((w) => w.id === prev)
+                        ? prev
+                        : (jsonMessage.watches[0] || {}).id));
+                } else if (jsonMessage.command === 'SetLayout') {
+                    setLayout(jsonMessage.layout);
+                    setServerError(null);
+                } else if (jsonMessage.command === 'Error') {
+                    console.error('Server reported an error:', jsonMessage.message);
+                    // The editor stays open to fix what was refused
+                    changeSent.current = false;
+                    setServerError(jsonMessage.message);
                 }
             } catch (error) {
                 console.error('Failed to parse WebSocket message:', error);
@@ -122,14 +146,124 @@ function App() {
         }
     };
 
+    // The layout is checked and kept by the server, which sends it back
+    const requestLayout = (next) => {
+        if (ws && isConnected) {
+            ws.send(JSON.stringify({ command: 'SetLayout', layout: next }));
+        } else {
+            console.log('WebSocket is not connected');
+        }
+    };
+
+    // Watches are checked by the server, which sends every client the new
+    // list, or this one an error
+    const sendWatchChange = (messageObject) => {
+        if (ws && isConnected) {
+            changeSent.current = true;
+            setServerError(null);
+            ws.send(JSON.stringify(messageObject));
+        } else {
+            console.log('WebSocket is not connected');
+        }
+    };
+
+    const saveWatch = (definition) => {
+        sendWatchChange({ command: editing === 'new' ? 'AddWatch' : 'UpdateWatch', definition });
+    };
+
+    const removeWatch = (id) => {
+        sendWatchChange({ command: 'RemoveWatch', id });
+    };
+
+    // Grid placement of a tile, watches without one flow into the free cells
+    const tileStyle = (id) => {
+        const tile = layout.tiles.find((t) => t.watch === id);
+        if (!tile) {
+            return { overflow: 'auto', border: '1px solid #ccc' };
+        }
+        return {
+            overflow: 'auto',
+            border: '1px solid #ccc',
+            gridColumn: `${tile.x + 1} / span ${tile.w}`,
+            gridRow: `${tile.y + 1} / span ${tile.h}`,
+        };
+    };
+
+    const grid = layout.mode === 'grid' && watches.length > 1;
+
+    // Sends a message about a watch, returning whether it could be sent
+    const sendTo = (id) => (messageObject) => {
+        if (ws && isConnected) {
+            ws.send(JSON.stringify({ ...messageObject, watch: id }));
+            return true;
+        }
+        console.log('WebSocket is not connected');
+        return false;
+    };
+
     return (
         <div>
-            {/* Title Banner Component */}
-            <TitleBanner leftTitle={leftTitle} centerTitle={centerTitle} rightTitle={rightTitle} />
-
-            {/* Render table with dynamic headers and data */}
-            <VigilantTable headers={headers} data={showData} />
+            {watches.length > 0 && (
+                <div style={{ display: 'flex', gap: '4px', padding: '6px 20px 0 20px', borderBottom: '1px solid #ccc' }}>
+                    {watches.map((w) => (
+                        <button
+                            key={w.id}
+                            onClick={() => setActiveWatch(w.id)}
+                            title={`Watch ${w.id}`}
+                            style={{
+                                fontWeight: w.id === activeWatch ? 'bold' : 'normal',
+                                background: alerting[w.id] ? '#f8d7da' : undefined,
+                            }}
+                        >
+                            {w.cmdline}{alerting[w.id] ? ` (${alerting[w.id]})` : ''}
+                        </button>
+                    ))}
+                    <button onClick={() => setEditing('new')} title="Add a watch">+</button>
+                    <button onClick={() => setEditing(activeWatch)} title="Change or remove the selected watch">Edit</button>
+                    <span style={{ flex: 1 }} />
+                    {watches.length > 1 && (
+                        <button onClick={() => requestLayout({ ...layout, mode: grid ? 'tabs' : 'grid' })}>
+                            {grid ? 'Tabs' : 'Dashboard'}
+                        </button>
+                    )}
+                    {grid && (
+                        <button onClick={() => setEditLayout(!editLayout)}>
+                            {editLayout ? 'Done' : 'Edit layout'}
+                        </button>
+                    )}
+                </div>
+            )}
+            {serverError && <div style={{ margin: '10px 20px 0 20px', color: 'red' }}>{serverError}</div>}
+            {editing !== null && (
+                <WatchEditor
+                    watch={editing === 'new' ? null : watches.find((w) => w.id === editing) || null}
+                    onSave={saveWatch}
+                    onRemove={removeWatch}
+                    onClose={() => setEditing(null)}
+                />
+            )}
+            {grid && editLayout && <LayoutBar layout={layout} watches={watches} onApply={requestLayout} />}
 
+            {/* Every view stays mounted so that hidden watches keep up with their runs */}
+            <div style={grid ? {
+                display: 'grid',
+                gridTemplateColumns: `repeat(${layout.columns}, 1fr)`,
+                gridAutoRows: '400px',
+                gap: '8px',
+                padding: '8px',
+            } : {}}>
+                {watches.map((w) => (
+                    <div key={w.id} style={grid ? tileStyle(w.id) : { display: w.id === activeWatch ? 'block' : 'none' }}>
+                        <WatchView
+                            subscribe={subscribe(w.id)}
+                            send={sendTo(w.id)}
+                            rightTitle={rightTitle}
+                            compact={grid}
+                            onAlerts={(count) => setAlerting((prev) => ({ ...prev, [w.id]: count }))}
+                        />
+                    </div>
+                ))}
+            </div>
         </div>
     );
 }
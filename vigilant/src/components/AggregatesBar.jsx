@@ -0,0 +1,35 @@
+import React, { useState, useEffect } from 'react';
+
+// Text box listing the footer aggregates, separated by semicolons, e.g.
+// "sum(%MEM); count(STATE == "ESTAB")". They are computed by the server.
+const AggregatesBar = ({ aggregates, onApply }) => {
+  const [text, setText] = useState((aggregates || []).join('; '));
+
+  useEffect(() => {
+    setText((aggregates || []).join('; '));
+  }, [aggregates]);
+
+  const handleKeyDown = (event) => {
+    if (event.key === 'Enter') {
+      onApply(text.split(';').map((a) => a.trim()).filter((a) => a !== ''));
+    } else if (event.key === 'Escape') {
+      setText('');
+      onApply([]);
+    }
+  };
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px' }}>
+      <input
+        type="text"
+        value={text}
+        placeholder='Footer, e.g. sum(%MEM); max(RSS); count(STATE == "ESTAB")'
+        onChange={(event) => setText(event.target.value)}
+        onKeyDown={handleKeyDown}
+        style={{ width: '100%', fontFamily: 'monospace', padding: '4px 8px', border: '1px solid #ccc' }}
+      />
+    </div>
+  );
+};
+
+export default AggregatesBar;
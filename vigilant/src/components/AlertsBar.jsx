@@ -0,0 +1,27 @@
+import React from 'react';
+
+// Alerts which fired and have not resolved yet, with the rows that made
+// them fire, or the lines matching a pattern. Table rows are also
+// highlighted in the table; hovering shows the whole row or line.
+const AlertsBar = ({ alerts }) => {
+  const rules = Object.keys(alerts);
+  if (rules.length === 0) {
+    return null;
+  }
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px', padding: '6px 10px', backgroundColor: '#f8d7da', color: '#842029', borderRadius: '4px' }}>
+      {rules.map((rule) => (
+        <div key={rule}>
+          <b>Alert {rule}</b>
+          {': '}
+          {alerts[rule].rows.map((row, index) => (
+            <span key={row.key} title={row.values.join(' ')}>{index > 0 && ', '}{row.key}</span>
+          ))}
+        </div>
+      ))}
+    </div>
+  );
+};
+
+export default AlertsBar;
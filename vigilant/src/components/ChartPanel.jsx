@@ -0,0 +1,103 @@
+import React, { useState, useEffect } from 'react';
+
+const COLORS = ['#007bff', '#dc3545', '#28a745', '#fd7e14', '#6f42c1', '#20c997', '#e83e8c', '#6c757d'];
+
+// Time ranges offered, in seconds back from now (null for everything kept)
+const RANGES = [
+  { label: 'Last 5 minutes', secs: 300 },
+  { label: 'Last 15 minutes', secs: 900 },
+  { label: 'Last hour', secs: 3600 },
+  { label: 'Everything', secs: null },
+];
+
+// Plots a numeric column over time for the selected rows. The history is
+// kept by the server and requested with QueryHistory whenever the selection
+// changes, and again on every refresh while the panel is open. The history
+// of the column for every row can also be exported for use elsewhere.
+const ChartPanel = ({ columns, rowKeys, result, onQuery, onExport, refreshSecs = 10 }) => {
+  const [column, setColumn] = useState('');
+  const [keys, setKeys] = useState([]);
+  const [range, setRange] = useState(RANGES[1].secs);
+
+  useEffect(() => {
+    if (!column || keys.length === 0) {
+      return undefined;
+    }
+    const query = () => {
+      const from = range ? Date.now() / 1000 - range : null;
+      onQuery(keys, column, from, null);
+    };
+    query();
+    const intervalId = setInterval(query, refreshSecs * 1000);
+    return () => clearInterval(intervalId);
+  }, [column, keys, range]);
+
+  const width = 700;
+  const height = 250;
+  const pad = 40;
+  const series = result && result.column === column ? result.series : [];
+  const points = series.flatMap((s) => s.points);
+  const tMin = Math.min(...points.map(([t]) => t));
+  const tMax = Math.max(...points.map(([t]) => t));
+  const vMin = Math.min(...points.map(([, v]) => v));
+  const vMax = Math.max(...points.map(([, v]) => v));
+  const x = (t) => pad + ((t - tMin) / (tMax - tMin || 1)) * (width - 2 * pad);
+  const y = (v) => height - pad - ((v - vMin) / (vMax - vMin || 1)) * (height - 2 * pad);
+
+  return (
+    <div style={{ margin: '10px 20px', padding: '10px', border: '1px solid #ccc' }}>
+      <div style={{ display: 'flex', gap: '10px', marginBottom: '10px' }}>
+        <select value={column} onChange={(event) => setColumn(event.target.value)}>
+          <option value="">Column…</option>
+          {columns.map((c) => <option key={c} value={c}>{c}</option>)}
+        </select>
+        <select
+          multiple
+          value={keys}
+          onChange={(event) => setKeys(Array.from(event.target.selectedOptions, (o) => o.value))}
+          style={{ minWidth: '200px', height: '5em' }}
+        >
+          {rowKeys.map((k) => <option key={k} value={k}>{k}</option>)}
+        </select>
+        <select value={range === null ? '' : range} onChange={(event) => setRange(event.target.value === '' ? null : Number(event.target.value))}>
+          {RANGES.map((r) => <option key={r.label} value={r.secs === null ? '' : r.secs}>{r.label}</option>)}
+        </select>
+        {['csv', 'jsonl'].map((format) => (
+          <button
+            key={format}
+            disabled={!column}
+            onClick={() => onExport([column], range ? Date.now() / 1000 - range : null, null, format)}
+          >
+            Export {format === 'csv' ? 'CSV' : 'JSON lines'}
+          </button>
+        ))}
+      </div>
+
+      {points.length > 0 ? (
+        <svg width={width} height={height}>
+          <line x1={pad} y1={height - pad} x2={width - pad} y2={height - pad} stroke="#999" />
+          <line x1={pad} y1={pad} x2={pad} y2={height - pad} stroke="#999" />
+          <text x={2} y={pad} fontSize="11">{vMax}</text>
+          <text x={2} y={height - pad} fontSize="11">{vMin}</text>
+          <text x={pad} y={height - pad + 15} fontSize="11">{new Date(tMin * 1000).toLocaleTimeString()}</text>
+          <text x={width - pad} y={height - pad + 15} fontSize="11" textAnchor="end">{new Date(tMax * 1000).toLocaleTimeString()}</text>
+          {series.map((s, i) => (
+            <g key={s.key}>
+              <polyline
+                fill="none"
+                stroke={COLORS[i % COLORS.length]}
+                strokeWidth="1.5"
+                points={s.points.map(([t, v]) => `${x(t).toFixed(1)},${y(v).toFixed(1)}`).join(' ')}
+              />
+              <text x={width - pad + 5} y={pad + i * 14} fontSize="11" fill={COLORS[i % COLORS.length]}>{s.key}</text>
+            </g>
+          ))}
+        </svg>
+      ) : (
+        <div>Pick a numeric column and one or more rows to plot.</div>
+      )}
+    </div>
+  );
+};
+
+export default ChartPanel;
@@ -0,0 +1,48 @@
+import React, { useState, useEffect } from 'react';
+
+// Parses "PID,COMMAND=Command,%CPU" into [{ name, label }] as the server expects
+const parseColumns = (text) =>
+  text.split(',')
+    .map((c) => c.trim())
+    .filter((c) => c !== '')
+    .map((c) => {
+      const eq = c.indexOf('=');
+      return eq < 0
+        ? { name: c, label: null }
+        : { name: c.slice(0, eq), label: c.slice(eq + 1) };
+    });
+
+// Text box selecting, ordering and renaming the columns the server sends.
+const ColumnsBar = ({ available, columns, onApply }) => {
+  const [text, setText] = useState(columns || '');
+
+  useEffect(() => {
+    setText(columns || '');
+  }, [columns]);
+
+  const handleKeyDown = (event) => {
+    if (event.key === 'Enter') {
+      const parsed = parseColumns(text);
+      onApply(parsed.length > 0 ? parsed : null);
+    } else if (event.key === 'Escape') {
+      setText('');
+      onApply(null);
+    }
+  };
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px' }}>
+      <input
+        type="text"
+        value={text}
+        placeholder={`Columns, e.g. ${(available || []).slice(0, 3).join(',')} (NAME=Label renames)`}
+        title={`Available: ${(available || []).join(', ')}`}
+        onChange={(event) => setText(event.target.value)}
+        onKeyDown={handleKeyDown}
+        style={{ width: '100%', fontFamily: 'monospace', padding: '4px 8px', border: '1px solid #ccc' }}
+      />
+    </div>
+  );
+};
+
+export default ColumnsBar;
@@ -0,0 +1,134 @@
+import React, { useState, useEffect } from 'react';
+
+const ADDED = '#d4edda';
+const REMOVED = '#f8d7da';
+const CHANGED = '#fff3cd';
+
+// Compares two stored runs. The server matches rows by key and returns the
+// rows added, removed and changed between them, shown either unified (one
+// table, old values struck through) or side by side.
+const DiffPanel = ({ first, last, result, onDiff }) => {
+  const [from, setFrom] = useState(Math.max(first, last - 1));
+  const [to, setTo] = useState(last);
+  const [sideBySide, setSideBySide] = useState(false);
+
+  useEffect(() => {
+    if (from !== to) {
+      onDiff(Math.min(from, to), Math.max(from, to));
+    }
+  }, [from, to]);
+
+  const time = (secs) => new Date(secs * 1000).toLocaleString();
+  const diff = result && result.from === Math.min(from, to) && result.to === Math.max(from, to) ? result : null;
+  const cell = { padding: '2px 8px', border: '1px solid #ddd' };
+
+  const picker = (label, value, setValue) => (
+    <label style={{ display: 'flex', alignItems: 'center', gap: '6px', flex: 1 }}>
+      {label}
+      <input type="range" min={first} max={last} value={value}
+        onChange={(event) => setValue(Number(event.target.value))} style={{ flex: 1 }} />
+      <span style={{ minWidth: '5em' }}>Run {value}</span>
+    </label>
+  );
+
+  const unified = (d) => (
+    <table style={{ borderCollapse: 'collapse', fontFamily: 'monospace' }}>
+      <thead>
+        <tr><th style={cell} />{d.headers.map((h, i) => <th key={i} style={cell}>{h}</th>)}</tr>
+      </thead>
+      <tbody>
+        {d.removed.map((row) => (
+          <tr key={`-${row.key}`} style={{ backgroundColor: REMOVED }}>
+            <td style={cell}>-</td>
+            {row.values.map((v, i) => <td key={i} style={cell}>{v}</td>)}
+          </tr>
+        ))}
+        {d.added.map((row) => (
+          <tr key={`+${row.key}`} style={{ backgroundColor: ADDED }}>
+            <td style={cell}>+</td>
+            {row.values.map((v, i) => <td key={i} style={cell}>{v}</td>)}
+          </tr>
+        ))}
+        {d.changed.map((row) => (
+          <tr key={`~${row.key}`}>
+            <td style={cell}>~</td>
+            {row.new.map((v, i) => (
+              <td key={i} style={{ ...cell, backgroundColor: row.cells.includes(i) ? CHANGED : undefined }}>
+                {row.cells.includes(i) && <s style={{ color: '#888', marginRight: '6px' }}>{row.old[i]}</s>}
+                {v}
+              </td>
+            ))}
+          </tr>
+        ))}
+      </tbody>
+    </table>
+  );
+
+  const sideTable = (headers, rows) => (
+    <table style={{ borderCollapse: 'collapse', fontFamily: 'monospace', flex: 1 }}>
+      <thead><tr>{headers.map((h, i) => <th key={i} style={cell}>{h}</th>)}</tr></thead>
+      <tbody>
+        {rows.map(({ key, values, color, cells }) => (
+          <tr key={key} style={{ backgroundColor: color }}>
+            {(values || headers.map(() => '')).map((v, i) => (
+              <td key={i} style={{ ...cell, backgroundColor: cells && cells.includes(i) ? CHANGED : undefined }}>{v}</td>
+            ))}
+          </tr>
+        ))}
+      </tbody>
+    </table>
+  );
+
+  // Both sides get a line for every row so that they stay aligned
+  const sideBySideView = (d) => {
+    const left = [];
+    const right = [];
+    d.removed.forEach((row) => {
+      left.push({ key: `-${row.key}`, values: row.values, color: REMOVED });
+      right.push({ key: `-${row.key}`, values: null });
+    });
+    d.added.forEach((row) => {
+      left.push({ key: `+${row.key}`, values: null });
+      right.push({ key: `+${row.key}`, values: row.values, color: ADDED });
+    });
+    d.changed.forEach((row) => {
+      left.push({ key: `~${row.key}`, values: row.old, cells: row.cells });
+      right.push({ key: `~${row.key}`, values: row.new, cells: row.cells });
+    });
+    return (
+      <div style={{ display: 'flex', gap: '20px' }}>
+        {sideTable(d.old_headers, left)}
+        {sideTable(d.headers, right)}
+      </div>
+    );
+  };
+
+  return (
+    <div style={{ margin: '10px 20px', padding: '10px', border: '1px solid #ccc' }}>
+      <div style={{ display: 'flex', gap: '20px', marginBottom: '10px' }}>
+        {picker('From', from, setFrom)}
+        {picker('To', to, setTo)}
+        <label>
+          <input type="checkbox" checked={sideBySide} onChange={(event) => setSideBySide(event.target.checked)} /> Side by side
+        </label>
+      </div>
+      {from === to ? (
+        <div>Pick two different runs to compare.</div>
+      ) : diff ? (
+        <>
+          <div style={{ marginBottom: '10px', color: '#555' }}>
+            {time(diff.from_timestamp)} to {time(diff.to_timestamp)}: {diff.added.length} added,
+            {' '}{diff.removed.length} removed, {diff.changed.length} changed
+          </div>
+          {diff.added.length + diff.removed.length + diff.changed.length === 0
+            ? <div>No differences.</div>
+            : sideBySide ? sideBySideView(diff) : unified(diff)}
+        </>
+      ) : (
+        <div>Comparing…</div>
+      )}
+    </div>
+  );
+};
+
+export default DiffPanel;
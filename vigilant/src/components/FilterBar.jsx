@@ -0,0 +1,41 @@
+import React, { useState, useEffect } from 'react';
+
+// Text box for the row filter. The expression is evaluated by the server,
+// which reports syntax errors or unknown columns back as an error message.
+const FilterBar = ({ filter, error, onApply }) => {
+  const [text, setText] = useState(filter || '');
+
+  useEffect(() => {
+    setText(filter || '');
+  }, [filter]);
+
+  const handleKeyDown = (event) => {
+    if (event.key === 'Enter') {
+      onApply(text.trim() === '' ? null : text);
+    } else if (event.key === 'Escape') {
+      setText('');
+      onApply(null);
+    }
+  };
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px' }}>
+      <input
+        type="text"
+        value={text}
+        placeholder='Filter, e.g. STATE == "ESTAB" && PORT > 1024, /regex/ or text'
+        onChange={(event) => setText(event.target.value)}
+        onKeyDown={handleKeyDown}
+        style={{
+          width: '100%',
+          fontFamily: 'monospace',
+          padding: '4px 8px',
+          border: error ? '1px solid #dc3545' : '1px solid #ccc',
+        }}
+      />
+      {error && <div style={{ color: '#dc3545', fontSize: '14px' }}>{error}</div>}
+    </div>
+  );
+};
+
+export default FilterBar;
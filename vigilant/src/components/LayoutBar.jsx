@@ -0,0 +1,78 @@
+import React, { useState, useEffect } from 'react';
+
+// Editor of the dashboard grid: its number of columns and, for every watch,
+// the cell its tile starts at and how many cells it spans. Watches left
+// without a column and row are placed in the first free cells. The server
+// checks the tiles fit and do not overlap, and keeps the layout.
+const LayoutBar = ({ layout, watches, onApply }) => {
+  const toRows = () => Object.fromEntries(watches.map((w) => {
+    const tile = layout.tiles.find((t) => t.watch === w.id);
+    return [w.id, tile
+      ? { x: String(tile.x + 1), y: String(tile.y + 1), w: String(tile.w), h: String(tile.h) }
+      : { x: '', y: '', w: '1', h: '1' }];
+  }));
+  const [columns, setColumns] = useState(String(layout.columns));
+  const [rows, setRows] = useState(toRows());
+
+  useEffect(() => {
+    setColumns(String(layout.columns));
+    setRows(toRows());
+  }, [layout, watches]);
+
+  const setField = (id, field, value) => {
+    setRows((prev) => ({ ...prev, [id]: { ...prev[id], [field]: value } }));
+  };
+
+  const apply = () => {
+    const tiles = watches
+      .filter((w) => rows[w.id] && rows[w.id].x !== '' && rows[w.id].y !== '')
+      .map((w) => ({
+        watch: w.id,
+        x: Number(rows[w.id].x) - 1,
+        y: Number(rows[w.id].y) - 1,
+        w: Number(rows[w.id].w) || 1,
+        h: Number(rows[w.id].h) || 1,
+      }));
+    onApply({ ...layout, columns: Number(columns) || 1, tiles });
+  };
+
+  const field = (id, name, title) => (
+    <input
+      type="number"
+      min="1"
+      value={rows[id] ? rows[id][name] : ''}
+      title={title}
+      placeholder={name === 'x' || name === 'y' ? 'auto' : ''}
+      onChange={(event) => setField(id, name, event.target.value)}
+      style={{ width: '4em' }}
+    />
+  );
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px' }}>
+      <div>
+        Columns <input type="number" min="1" max="12" value={columns}
+          onChange={(event) => setColumns(event.target.value)} style={{ width: '4em' }} />
+        <button onClick={apply} style={{ marginLeft: '10px' }}>Apply layout</button>
+      </div>
+      <table style={{ marginTop: '6px' }}>
+        <thead>
+          <tr><th>Watch</th><th>Column</th><th>Row</th><th>Width</th><th>Height</th></tr>
+        </thead>
+        <tbody>
+          {watches.map((w) => (
+            <tr key={w.id}>
+              <td style={{ fontFamily: 'monospace' }}>{w.cmdline}</td>
+              <td>{field(w.id, 'x', 'Column of the left edge')}</td>
+              <td>{field(w.id, 'y', 'Row of the top edge')}</td>
+              <td>{field(w.id, 'w', 'Columns spanned')}</td>
+              <td>{field(w.id, 'h', 'Rows spanned')}</td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+    </div>
+  );
+};
+
+export default LayoutBar;
@@ -0,0 +1,27 @@
+import React from 'react';
+
+const SPEEDS = [0.5, 1, 2, 5, 10, 60];
+
+// Controls for a recording being played back by the server: pause, step
+// to the next run, and how much faster than recorded to play.
+const ReplayBar = ({ state, onSet, onStep }) => {
+  const when = state.timestamp ? new Date(state.timestamp * 1000).toLocaleString() : '';
+  const done = state.position >= state.count;
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px', display: 'flex', alignItems: 'center', gap: '10px' }}>
+      <b>Replay</b>
+      <button onClick={() => onSet({ paused: !state.paused })} disabled={done}>
+        {state.paused ? 'Play' : 'Pause'}
+      </button>
+      <button onClick={onStep} disabled={done}>Step</button>
+      <select value={state.speed} onChange={(event) => onSet({ speed: Number(event.target.value) })}>
+        {(SPEEDS.includes(state.speed) ? SPEEDS : [...SPEEDS, state.speed].sort((a, b) => a - b))
+          .map((s) => <option key={s} value={s}>{s}x</option>)}
+      </select>
+      <span>Run {state.position} of {state.count}{when && `, recorded ${when}`}{done && ' (finished)'}</span>
+    </div>
+  );
+};
+
+export default ReplayBar;
@@ -0,0 +1,52 @@
+import React from 'react';
+import { MDBTable, MDBTableHead, MDBTableBody } from 'mdb-react-ui-kit';
+import TextView from './TextView';
+
+// Shows a past run, as a table or as text like the live view, with the
+// cells that differ from the run before it highlighted.
+const SnapshotView = ({ snapshot, raw }) => {
+  const when = new Date(snapshot.timestamp * 1000).toLocaleString();
+  const status = snapshot.status === null ? 'killed' : `exit ${snapshot.status}`;
+
+  return (
+    <div>
+      <div style={{ margin: '10px 20px 0 20px', color: '#555' }}>
+        Run {snapshot.run_id} at {when}, took {snapshot.duration.toFixed(2)}s, {status}
+      </div>
+      {raw ? (
+        <TextView lines={snapshot.lines} changed={snapshot.changed_lines} />
+      ) : (
+        <div style={{ margin: '20px' }}>
+          <MDBTable striped hover>
+            <MDBTableHead>
+              <tr>
+                {snapshot.headers.map((header, index) => (
+                  <th key={index} style={{ fontWeight: 'bold' }}>{header}</th>
+                ))}
+              </tr>
+            </MDBTableHead>
+            <MDBTableBody>
+              {snapshot.rows.map((row, rowIndex) => {
+                const changed = new Set(snapshot.changed[rowIndex] || []);
+                return (
+                  <tr key={snapshot.keys[rowIndex] || rowIndex}>
+                    {row.map((cell, cellIndex) => (
+                      <td key={cellIndex} style={changed.has(cellIndex) ? { backgroundColor: '#fff3cd' } : undefined}>
+                        {cell}
+                      </td>
+                    ))}
+                  </tr>
+                );
+              })}
+            </MDBTableBody>
+          </MDBTable>
+        </div>
+      )}
+      {snapshot.stderr.length > 0 && (
+        <pre style={{ margin: '0 20px 20px 20px', color: '#dc3545' }}>{snapshot.stderr.join('\n')}</pre>
+      )}
+    </div>
+  );
+};
+
+export default SnapshotView;
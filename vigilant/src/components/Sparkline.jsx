@@ -0,0 +1,34 @@
+import React from 'react';
+
+// Small inline chart of the recent values of a cell. Hovering shows the
+// values with the time they were taken.
+const Sparkline = ({ points, width = 60, height = 16 }) => {
+  if (!points || points.length < 2) {
+    return null;
+  }
+
+  const values = points.map(([, value]) => value);
+  const min = Math.min(...values);
+  const max = Math.max(...values);
+  const span = max - min || 1; // Flat lines are drawn in the middle
+
+  const path = points.map(([, value], i) => {
+    const x = (i / (points.length - 1)) * width;
+    const y = max === min ? height / 2 : height - ((value - min) / span) * height;
+    return `${i === 0 ? 'M' : 'L'}${x.toFixed(1)},${y.toFixed(1)}`;
+  }).join(' ');
+
+  const tooltip = points
+    .slice(-10)
+    .map(([time, value]) => `${new Date(time * 1000).toLocaleTimeString()}  ${value}`)
+    .join('\n');
+
+  return (
+    <svg width={width} height={height} style={{ marginLeft: '6px', verticalAlign: 'middle' }}>
+      <title>{tooltip}</title>
+      <path d={path} fill="none" stroke="#007bff" strokeWidth="1" />
+    </svg>
+  );
+};
+
+export default Sparkline;
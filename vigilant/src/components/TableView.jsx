@@ -1,8 +1,37 @@
 import React, { useState, useEffect } from 'react';
 import { MDBTable, MDBTableHead, MDBTableBody } from 'mdb-react-ui-kit';
 import 'mdb-react-ui-kit/dist/css/mdb.min.css'; // Import the CSS for proper styling
+import Sparkline from './Sparkline';
+
+const VigilantTable = ({ headers, names = [], kinds = [], data, history = {}, sort, onSort, rates = [], onToggleRate, footer = [], groups = null, alertKeys = [] }) => {
+  const [expanded, setExpanded] = useState({}); // Group value -> shown or not
+
+  const toggleGroup = (value) =>
+    setExpanded((prev) => ({ ...prev, [value]: !prev[value] }));
+
+  // Counters can get a per-second rate column, rate columns themselves cannot
+  const canRate = (index) =>
+    ['Integer', 'Float', 'Size'].includes(kinds[index]) && !(names[index] || '').endsWith('/s');
+
+  // Numbers, sizes, durations etc. read better right aligned
+  const cellStyle = (cellIndex) =>
+    kinds[cellIndex] && kinds[cellIndex] !== 'Text' ? { textAlign: 'right' } : undefined;
+
+  // Rows which made an alert fire stand out
+  const rowStyle = (row) =>
+    row.key && alertKeys.includes(row.key) ? { backgroundColor: '#f8d7da' } : undefined;
+
+  const renderRow = (row, rowIndex) => (
+    <tr key={row.key || rowIndex} style={rowStyle(row)}>
+      {row.values.map((cell, cellIndex) => (
+        <td key={cellIndex} style={cellStyle(cellIndex)}>
+          {cell || '-'}{/* Fallback to "-" if cell is empty */}
+          {history[row.key] && <Sparkline points={history[row.key][names[cellIndex] || headers[cellIndex]]} />}
+        </td>
+      ))}
+    </tr>
+  );
 
-const VigilantTable = ({ headers, data }) => {
   useEffect(() => {
     console.log('Headers received in VigilantTable:', headers);
     console.log('Data received in VigilantTable:', data);
@@ -15,20 +44,46 @@ const VigilantTable = ({ headers, data }) => {
       <MDBTableHead>
         <tr>
           {headers.map((header, index) => (
-            <th key={index} style={{ fontWeight: 'bold' }}>{header}</th>
+            <th
+              key={index}
+              style={{ fontWeight: 'bold', cursor: onSort ? 'pointer' : 'default' }}
+              onClick={() => onSort && onSort(names[index] || header)}
+            >
+              {header}
+              {sort && sort.column === (names[index] || header) ? (sort.direction === 'asc' ? ' \u25B2' : ' \u25BC') : ''}
+              {onToggleRate && canRate(index) && (
+                <span
+                  title="Show the per-second rate of this counter"
+                  onClick={(event) => { event.stopPropagation(); onToggleRate(names[index] || header); }}
+                  style={{ marginLeft: '6px', fontSize: '11px', opacity: rates.includes(names[index]) ? 1 : 0.4 }}
+                >
+                  /s
+                </span>
+              )}
+            </th>
           ))}
         </tr>
       </MDBTableHead>
 
         <MDBTableBody>
-          {data && data.length > 0 ? (
+          {groups ? (
+            // Grouped by the server: a summary line per group, rows when expanded
+            groups.map((group) => (
+              <React.Fragment key={group.value}>
+                <tr onClick={() => toggleGroup(group.value)} style={{ cursor: 'pointer', backgroundColor: '#e9ecef' }}>
+                  <td colSpan={headers.length || 1}>
+                    {expanded[group.value] ? '\u25BE ' : '\u25B8 '}
+                    <b>{group.value || '(empty)'}</b> ({group.count})
+                    {group.aggregates.map((cell) => `   ${cell.label} = ${cell.text}`).join('')}
+                  </td>
+                </tr>
+                {expanded[group.value] && group.rows.map(renderRow)}
+              </React.Fragment>
+            ))
+          ) : data && data.length > 0 ? (
             data.map((row, rowIndex) => (
               row.values ? ( // Access the 'values' array from the row object
-                <tr key={rowIndex}>
-                  {row.values.map((cell, cellIndex) => (
-                    <td key={cellIndex}>{cell || '-'}</td> // Fallback to "-" if cell is empty
-                  ))}
-                </tr>
+                renderRow(row, rowIndex)
               ) : (
                 <tr key={rowIndex}>
                   <td colSpan={headers.length || 1}>Invalid row format</td>
@@ -41,6 +96,30 @@ const VigilantTable = ({ headers, data }) => {
             </tr>
           )}
         </MDBTableBody>
+
+        {footer.length > 0 && (
+          <tfoot style={{ fontWeight: 'bold', borderTop: '2px solid #333' }}>
+            <tr>
+              {headers.map((header, index) => (
+                <td key={index} style={cellStyle(index)}>
+                  {footer
+                    .filter((cell) => cell.column === (names[index] || header))
+                    .map((cell) => <div key={cell.label} title={cell.label}>{cell.text}</div>)}
+                </td>
+              ))}
+            </tr>
+            {footer.some((cell) => !cell.column) && (
+              <tr>
+                <td colSpan={headers.length || 1}>
+                  {footer
+                    .filter((cell) => !cell.column)
+                    .map((cell) => `${cell.label} = ${cell.text}`)
+                    .join('    ')}
+                </td>
+              </tr>
+            )}
+          </tfoot>
+        )}
       </MDBTable>
     </div>
   );
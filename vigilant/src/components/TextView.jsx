@@ -0,0 +1,27 @@
+import React from 'react';
+
+// Renders raw command output the way watch(1) does: monospace, whitespace
+// preserved, with the lines that changed on the last refresh highlighted.
+const TextView = ({ lines, changed }) => {
+  const changedSet = new Set(changed || []);
+
+  return (
+    <pre style={{
+      margin: '20px',
+      fontFamily: 'monospace',
+      whiteSpace: 'pre', // Keep alignment exactly as the command printed it
+      overflowX: 'auto',
+    }}>
+      {lines.map((line, index) => (
+        <div
+          key={index}
+          style={changedSet.has(index) ? { backgroundColor: '#fff3cd' } : undefined}
+        >
+          {line || ' '}
+        </div>
+      ))}
+    </pre>
+  );
+};
+
+export default TextView;
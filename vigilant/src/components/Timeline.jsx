@@ -0,0 +1,32 @@
+import React from 'react';
+
+// Slider over the runs kept by the server. Moving it away from the newest
+// run pins the view to that run; Live goes back to following updates, which
+// are recorded all along.
+const Timeline = ({ first, last, runId, onSelect, comparing, onCompare }) => {
+  if (first === undefined || last === undefined || first === last) {
+    return null;
+  }
+  const live = runId === null;
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px', display: 'flex', alignItems: 'center', gap: '10px' }}>
+      <input
+        type="range"
+        min={first}
+        max={last}
+        value={live ? last : runId}
+        onChange={(event) => {
+          const id = Number(event.target.value);
+          onSelect(id === last ? null : id);
+        }}
+        style={{ flex: 1 }}
+      />
+      <span style={{ minWidth: '8em' }}>{live ? 'Live' : `Run ${runId} of ${last}`}</span>
+      <button onClick={() => onSelect(null)} disabled={live}>Live</button>
+      <button onClick={onCompare}>{comparing ? 'Hide diff' : 'Compare runs'}</button>
+    </div>
+  );
+};
+
+export default Timeline;
@@ -0,0 +1,67 @@
+import React, { useState, useEffect } from 'react';
+
+// Form adding a watch, or changing the command, interval or display of one.
+// The server checks the definition, reports what is wrong with it as an
+// error, and otherwise starts the watch over for every client.
+const WatchEditor = ({ watch, onSave, onRemove, onClose }) => {
+  const [cmdline, setCmdline] = useState('');
+  const [secs, setSecs] = useState('');
+  const [raw, setRaw] = useState(false);
+
+  useEffect(() => {
+    setCmdline(watch ? watch.cmdline : '');
+    setSecs(watch && watch.interval ? String(watch.interval) : '');
+    setRaw(watch ? watch.raw : false);
+  }, [watch]);
+
+  const save = () => {
+    // Table settings of a watch being changed are kept
+    onSave({
+      ...(watch || {}),
+      cmdline,
+      interval: secs.trim() === '' ? null : Number(secs),
+      raw,
+    });
+  };
+
+  const handleKeyDown = (event) => {
+    if (event.key === 'Enter') {
+      save();
+    } else if (event.key === 'Escape') {
+      onClose();
+    }
+  };
+
+  return (
+    <div style={{ margin: '10px 20px 0 20px', display: 'flex', alignItems: 'center', gap: '10px' }}>
+      <b>{watch ? `Watch ${watch.id}` : 'New watch'}</b>
+      <input
+        type="text"
+        value={cmdline}
+        placeholder="Command, e.g. df -h"
+        autoFocus
+        onChange={(event) => setCmdline(event.target.value)}
+        onKeyDown={handleKeyDown}
+        style={{ flex: 1, fontFamily: 'monospace', padding: '4px 8px', border: '1px solid #ccc' }}
+      />
+      <input
+        type="number"
+        min="1"
+        value={secs}
+        placeholder="secs"
+        title="Seconds between runs, the global interval when empty"
+        onChange={(event) => setSecs(event.target.value)}
+        onKeyDown={handleKeyDown}
+        style={{ width: '5em' }}
+      />
+      <label>
+        <input type="checkbox" checked={raw} onChange={(event) => setRaw(event.target.checked)} /> Text
+      </label>
+      <button onClick={save}>{watch ? 'Save' : 'Add'}</button>
+      {watch && <button onClick={() => onRemove(watch.id)}>Remove</button>}
+      <button onClick={onClose}>Cancel</button>
+    </div>
+  );
+};
+
+export default WatchEditor;
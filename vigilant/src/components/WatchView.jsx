@@ -0,0 +1,333 @@
+import React, { useState, useEffect } from 'react';
+import VigilantTable from './TableView';
+import TitleBanner from './TitleBanner';
+import TextView from './TextView';
+import FilterBar from './FilterBar';
+import ColumnsBar from './ColumnsBar';
+import ChartPanel from './ChartPanel';
+import AggregatesBar from './AggregatesBar';
+import Timeline from './Timeline';
+import SnapshotView from './SnapshotView';
+import DiffPanel from './DiffPanel';
+import ReplayBar from './ReplayBar';
+import AlertsBar from './AlertsBar';
+
+// Hands text produced by the server to the browser as a download
+const saveFile = (filename, content) => {
+    const url = URL.createObjectURL(new Blob([content], { type: 'text/plain' }));
+    const link = document.createElement('a');
+    link.href = url;
+    link.download = filename;
+    link.click();
+    URL.revokeObjectURL(url);
+};
+
+// Everything shown about one watched command: its table or text, and the
+// panels around it. Messages about the watch are handed over by App through
+// subscribe, and send addresses the messages to the server to the watch.
+// A compact view, tiled on the dashboard, leaves out the editing panels.
+function WatchView({ subscribe, send, rightTitle, onAlerts, compact = false }) {
+    const [showData, setShowData] = useState([]);
+    const [cellHistory, setCellHistory] = useState({}); // Points of the numeric cells, by row key and column
+    const [headers, setHeaders] = useState([]);
+    const [kinds, setKinds] = useState([]); // Inferred type of each column
+    const [names, setNames] = useState([]); // Column names in the command output, headers may be renamed
+    const [available, setAvailable] = useState([]);
+    const [columns, setColumns] = useState(null); // Column selection as typed, e.g. "PID,COMMAND=Command"
+    const [rates, setRates] = useState([]); // Counter columns followed by a COLUMN/s rate column
+    const [aggregates, setAggregates] = useState([]);
+    const [footer, setFooter] = useState([]); // Aggregates computed by the server
+    const [groupBy, setGroupBy] = useState(null);
+    const [groups, setGroups] = useState(null); // Set when the server groups the rows
+    const [showChart, setShowChart] = useState(false);
+    const [chartResult, setChartResult] = useState(null);
+    const [timeline, setTimeline] = useState({}); // { first, last } run ids kept by the server
+    const [snapshotId, setSnapshotId] = useState(null); // Run looked at, null when live
+    const [snapshot, setSnapshot] = useState(null);
+    const [showDiff, setShowDiff] = useState(false);
+    const [runDiff, setRunDiff] = useState(null);
+    const [replay, setReplay] = useState(null); // Set when the server plays back a recording
+    const [exportFormat, setExportFormat] = useState('csv');
+    const [alerts, setAlerts] = useState({}); // Firing alerts by rule, with their rows
+    const [sort, setSort] = useState(null);   // { column, direction } applied by the server
+    const [filter, setFilter] = useState(null);
+    const [serverError, setServerError] = useState(null);
+    const [textLines, setTextLines] = useState(null); // Set when the server is in raw text mode
+    const [changedLines, setChangedLines] = useState([]);
+    const [leftTitle, setLeftTitle] = useState('Every 10.0 secs');
+    const [centerTitle, setCenterTitle] = useState('Main Title');
+
+    // The server starts a watch over with its title, after it was changed
+    // or the connection came back; nothing shown before still holds
+    const resetView = () => {
+        setShowData([]);
+        setCellHistory({});
+        setHeaders([]);
+        setKinds([]);
+        setNames([]);
+        setAvailable([]);
+        setColumns(null);
+        setRates([]);
+        setAggregates([]);
+        setFooter([]);
+        setGroupBy(null);
+        setGroups(null);
+        setChartResult(null);
+        setTimeline({});
+        setSnapshotId(null);
+        setSnapshot(null);
+        setRunDiff(null);
+        setReplay(null);
+        setAlerts({});
+        setSort(null);
+        setFilter(null);
+        setServerError(null);
+        setTextLines(null);
+        setChangedLines([]);
+    };
+
+    const handleMessage = (jsonMessage) => {
+        if (jsonMessage.command === 'SetTitle') {
+            resetView();
+            if (jsonMessage.left) setLeftTitle(jsonMessage.left);
+            if (jsonMessage.center) setCenterTitle(jsonMessage.center);
+            console.log("Done setting Title ", jsonMessage);
+        } else if (jsonMessage.command === 'SetHeaders') {
+            console.log("Setting headers");
+            setHeaders(jsonMessage.headers); // Update headers
+            setKinds(jsonMessage.kinds || []);
+            setNames(jsonMessage.names || jsonMessage.headers);
+            setAvailable(jsonMessage.available || []);
+            setRates(jsonMessage.rates || []);
+            setAggregates(jsonMessage.aggregates || []);
+            setGroupBy(jsonMessage.group_by || null);
+            if (!jsonMessage.group_by) {
+                setGroups(null);
+            }
+            if (!jsonMessage.aggregates || jsonMessage.aggregates.length === 0) {
+                setFooter([]);
+            }
+            setSort(jsonMessage.sort || null);
+            setFilter(jsonMessage.filter || null);
+            // A new connection checks its rules from scratch
+            setAlerts((prev) => Object.fromEntries(Object.entries(prev)
+                .filter(([rule]) => (jsonMessage.alerts || []).includes(rule))));
+        } else if (jsonMessage.command === 'SetText') {
+            setTextLines(jsonMessage.lines);
+            setChangedLines(jsonMessage.changed);
+        } else if (jsonMessage.command === 'SetCellHistory') {
+            setCellHistory(jsonMessage.rows);
+        } else if (jsonMessage.command === 'AppendCellHistory') {
+            // Each run only brings its new point, rows left out went away
+            const { limit, time, rows } = jsonMessage;
+            setCellHistory((prev) => Object.fromEntries(Object.entries(rows).map(([key, values]) => {
+                const columns = { ...(prev[key] || {}) };
+                Object.entries(values).forEach(([column, value]) => {
+                    const points = columns[column] || [];
+                    if (points.length === 0 || points[points.length - 1][0] < time) {
+                        columns[column] = [...points, [time, value]].slice(-limit);
+                    }
+                });
+                return [key, columns];
+            })));
+        } else if (jsonMessage.command === 'SetGroups') {
+            setGroups(jsonMessage.groups);
+        } else if (jsonMessage.command === 'SetFooter') {
+            setFooter(jsonMessage.cells);
+        } else if (jsonMessage.command === 'AlertFired') {
+            const { rule, headers, rows } = jsonMessage;
+            setAlerts((prev) => ({ ...prev, [rule]: { headers, rows } }));
+        } else if (jsonMessage.command === 'AlertResolved') {
+            setAlerts((prev) => {
+                const { [jsonMessage.rule]: resolved, ...rest } = prev;
+                return rest;
+            });
+        } else if (jsonMessage.command === 'SetTimeline') {
+            setTimeline({ first: jsonMessage.first, last: jsonMessage.last });
+        } else if (jsonMessage.command === 'SetSnapshot') {
+            setSnapshot(jsonMessage);
+        } else if (jsonMessage.command === 'SetReplayState') {
+            setReplay(jsonMessage);
+        } else if (jsonMessage.command === 'ExportResult') {
+            saveFile(jsonMessage.filename, jsonMessage.content);
+        } else if (jsonMessage.command === 'SetRunDiff') {
+            setRunDiff(jsonMessage);
+        } else if (jsonMessage.command === 'HistoryResult') {
+            setChartResult(jsonMessage);
+        } else if (jsonMessage.command === 'SetRowCount') {
+            setShowData((prevData) => prevData.slice(0, jsonMessage.count));
+        } else if (jsonMessage.command === 'Error') {
+            console.error('Server reported an error:', jsonMessage.message);
+            setServerError(jsonMessage.message);
+        } else if (jsonMessage.command === 'SetDataRow') {
+            const { index, key, values, numbers } = jsonMessage;
+            console.log("Setting data");
+            setShowData((prevData) => {
+                const updatedData = [...prevData];
+                if (updatedData[index]) {
+                  // Update the existing entry using the values array
+                  updatedData[index] = { ...updatedData[index], key, values, numbers };
+                } else {
+                  // Add a new entry with the values array
+                  updatedData.push({ key, values, numbers });
+                }
+                return updatedData;
+              });
+        }
+    };
+
+    useEffect(() => subscribe(handleMessage), []);
+
+    // The tab shows whether something is wrong with a watch out of sight
+    useEffect(() => {
+        onAlerts(Object.keys(alerts).length);
+    }, [alerts]);
+
+    // Sorting is done by the server on the typed values, so that it survives
+    // refreshes; clicking a header cycles ascending, descending and unsorted.
+    const requestSort = (column) => {
+        let next = { column, direction: 'asc' };
+        if (sort && sort.column === column) {
+            next = sort.direction === 'asc' ? { column, direction: 'desc' } : null;
+        }
+        const messageObject = next
+            ? { command: 'SetSort', column: next.column, direction: next.direction }
+            : { command: 'SetSort', column: null, direction: 'asc' };
+        if (send(messageObject)) {
+            setSort(next);
+        }
+    };
+
+    const requestFilter = (text) => {
+        if (send({ command: 'SetFilter', filter: text })) {
+            setFilter(text);
+            setServerError(null);
+        }
+    };
+
+    const requestColumns = (specs) => {
+        if (send({ command: 'SetColumns', columns: specs })) {
+            setColumns(specs ? specs.map((c) => (c.label ? `${c.name}=${c.label}` : c.name)).join(',') : null);
+            setServerError(null);
+        }
+    };
+
+    const toggleRate = (column) => {
+        const enabled = !rates.includes(column);
+        if (send({ command: 'SetRate', column, enabled })) {
+            setServerError(null);
+        }
+    };
+
+    const requestAggregates = (list) => {
+        if (send({ command: 'SetAggregates', aggregates: list })) {
+            setServerError(null);
+        }
+    };
+
+    const requestGroupBy = (column) => {
+        if (send({ command: 'SetGroupBy', column })) {
+            setServerError(null);
+        }
+    };
+
+    const queryHistory = (keys, column, from, to) => {
+        send({ command: 'QueryHistory', keys, column, from, to });
+    };
+
+    const selectRun = (runId) => {
+        setSnapshotId(runId);
+        if (runId === null) {
+            setSnapshot(null);
+        } else {
+            send({ command: 'GetSnapshot', run_id: runId });
+        }
+    };
+
+    const diffRuns = (from, to) => {
+        send({ command: 'DiffRuns', from, to });
+    };
+
+    const setReplayOptions = ({ paused = null, speed = null }) => {
+        send({ command: 'SetReplay', paused, speed });
+    };
+
+    // Exports the table as shown, after filter, column selection and sort
+    const requestExport = () => {
+        send({ command: 'Export', format: exportFormat });
+    };
+
+    const exportHistory = (columns, from, to, format) => {
+        send({ command: 'ExportHistory', columns, from, to, format });
+    };
+
+    const stepReplay = () => {
+        send({ command: 'StepReplay' });
+    };
+
+    return (
+        <div>
+            {/* Title Banner Component */}
+            <TitleBanner leftTitle={leftTitle} centerTitle={centerTitle} rightTitle={rightTitle} />
+
+            <AlertsBar alerts={alerts} />
+            {replay && <ReplayBar state={replay} onSet={setReplayOptions} onStep={stepReplay} />}
+            {!compact && <Timeline first={timeline.first} last={timeline.last} runId={snapshotId} onSelect={selectRun}
+                comparing={showDiff} onCompare={() => setShowDiff(!showDiff)} />}
+            {!compact && showDiff && timeline.last !== undefined && (
+                <DiffPanel first={timeline.first} last={timeline.last} result={runDiff} onDiff={diffRuns} />
+            )}
+
+            {/* Render raw text when the output has no columns, a table otherwise */}
+            {!compact && snapshotId !== null && snapshot ? (
+                <SnapshotView snapshot={snapshot} raw={textLines !== null} />
+            ) : textLines ? (
+                <TextView lines={textLines} changed={changedLines} />
+            ) : (
+                <>
+                    {!compact && <>
+                        <FilterBar filter={filter} error={serverError} onApply={requestFilter} />
+                        <ColumnsBar available={available} columns={columns} onApply={requestColumns} />
+                        <AggregatesBar aggregates={aggregates} onApply={requestAggregates} />
+                        <div style={{ margin: '10px 20px 0 20px' }}>
+                            <button onClick={() => setShowChart(!showChart)}>
+                                {showChart ? 'Hide chart' : 'Chart'}
+                            </button>
+                            <select
+                                value={groupBy || ''}
+                                onChange={(event) => requestGroupBy(event.target.value || null)}
+                                style={{ marginLeft: '10px' }}
+                            >
+                                <option value="">No grouping</option>
+                                {available.map((c) => <option key={c} value={c}>Group by {c}</option>)}
+                            </select>
+                            <select value={exportFormat} onChange={(event) => setExportFormat(event.target.value)} style={{ marginLeft: '10px' }}>
+                                <option value="csv">CSV</option>
+                                <option value="jsonl">JSON lines</option>
+                                <option value="markdown">Markdown</option>
+                                <option value="html">HTML</option>
+                            </select>
+                            <button onClick={requestExport}>Export</button>
+                        </div>
+                    </>}
+                    {!compact && showChart && (
+                        <ChartPanel
+                            columns={names.filter((n, i) => kinds[i] && kinds[i] !== 'Text')}
+                            rowKeys={showData.map((row) => row.key).filter((k) => k)}
+                            result={chartResult}
+                            onQuery={queryHistory}
+                            onExport={exportHistory}
+                        />
+                    )}
+                    <VigilantTable headers={headers} names={names} kinds={kinds} data={showData} history={cellHistory} sort={sort} onSort={requestSort}
+                        rates={rates} onToggleRate={toggleRate} footer={footer}
+                        groups={groups}
+                        alertKeys={Object.values(alerts).flatMap((alert) => alert.rows.map((row) => row.key))} />
+                </>
+            )}
+
+        </div>
+    );
+}
+
+export default WatchView;